@@ -0,0 +1,262 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gtk4::Application;
+use gtk4_layer_shell::Edge;
+
+use crate::{
+    utils::tag_label,
+    widgets::{Plate, Slab, Tag},
+    Builder, Text, Threshold, Variable,
+};
+
+/// The window an `Alert` raises.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Popup {
+    /// A `Slab`, built once and shown again on every alert.
+    #[default]
+    Slab,
+    /// A new `Plate` for every alert.
+    Plate,
+}
+
+/// Decides when an `Alert` fires, given the values it sees.
+struct Trigger {
+    threshold: Threshold,
+    hysteresis: f64,
+    cooldown: Duration,
+    /// Cleared once fired, until the value clears the threshold by the hysteresis.
+    armed: bool,
+    last_fired: Option<Instant>,
+}
+
+impl Trigger {
+    fn new(threshold: Threshold, hysteresis: f64, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            hysteresis,
+            cooldown,
+            armed: true,
+            last_fired: None,
+        }
+    }
+
+    /// Takes in a new value seen at `now`, returning true if the alert should fire for it.
+    fn update(&mut self, value: f64, now: Instant) -> bool {
+        if !self.armed {
+            self.armed = self.threshold.clears(value, self.hysteresis);
+            return false;
+        }
+
+        let cooled_down = self
+            .last_fired
+            .filter(|fired| now.duration_since(*fired) < self.cooldown)
+            .is_none();
+
+        if !self.threshold.matches(value) || !cooled_down {
+            return false;
+        }
+
+        self.armed = false;
+        self.last_fired = Some(now);
+
+        true
+    }
+}
+
+/// Shows a message in a Slab or Plate whenever a value crosses a threshold, e.g. the battery
+/// dropping below 10% or a disk filling up past 95%.
+///
+/// Once raised, an alert only fires again after the value has moved back past the threshold by
+/// more than its hysteresis, and never more often than its cooldown allows.
+pub struct Alert {
+    factory: Application,
+    title: String,
+    threshold: Threshold,
+    message_fn: Rc<dyn Fn(f64) -> Text>,
+    margins: Vec<(Edge, i32)>,
+    anchors: Vec<(Edge, bool)>,
+    duration: u64,
+    popup: Popup,
+    hysteresis: f64,
+    cooldown: Duration,
+}
+
+impl Alert {
+    /// Creates a new `Alert`, which shows the message returned by `message_fn` for `duration`
+    /// seconds whenever the threshold is crossed. The message's label is named after the
+    /// threshold's class, for styling.
+    pub fn new<F, T>(
+        factory: Application,
+        title: &str,
+        threshold: Threshold,
+        message_fn: F,
+        margins: Vec<(Edge, i32)>,
+        anchors: Vec<(Edge, bool)>,
+        duration: u64,
+    ) -> Self
+    where
+        F: Fn(f64) -> T + 'static,
+        T: Into<Text>,
+    {
+        Self {
+            factory,
+            title: title.to_string(),
+            threshold,
+            message_fn: Rc::new(move |value| message_fn(value).into()),
+            margins,
+            anchors,
+            duration,
+            popup: Popup::default(),
+            hysteresis: 0.0,
+            cooldown: Duration::from_secs(300),
+        }
+    }
+
+    /// Sets the window to raise. Defaults to a `Slab`.
+    pub fn popup(mut self, popup: Popup) -> Self {
+        self.popup = popup;
+        self
+    }
+
+    /// Sets how far the value has to move back past the threshold before the alert can fire
+    /// again, so a value hovering around the threshold doesn't keep raising it. Defaults to 0.
+    pub fn hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Sets the least number of seconds between two alerts. Defaults to 5 minutes.
+    pub fn cooldown(mut self, seconds: u64) -> Self {
+        self.cooldown = Duration::from_secs(seconds);
+        self
+    }
+
+    /// Raises the alert whenever the value read from a `Variable` crosses the threshold.
+    /// `value_fn` may return `None` while there is no value, e.g. without a battery.
+    ///
+    /// Subscribing keeps polled variables running even while no Tag shows them.
+    pub fn watch<T, F>(self, variable: &Variable<T>, value_fn: F)
+    where
+        T: Clone + PartialEq + 'static,
+        F: Fn(&T) -> Option<f64> + 'static,
+    {
+        let show = self.popup_fn();
+        let trigger = RefCell::new(Trigger::new(self.threshold, self.hysteresis, self.cooldown));
+
+        variable.subscribe(move |value| {
+            let Some(value) = value_fn(value) else {
+                return;
+            };
+
+            if trigger.borrow_mut().update(value, Instant::now()) {
+                show(value);
+            }
+        });
+    }
+
+    /// Returns a closure raising the popup with the message for a value.
+    fn popup_fn(&self) -> Box<dyn Fn(f64)> {
+        let message_fn = Rc::clone(&self.message_fn);
+        let class_name = self.threshold.class().to_string();
+
+        match self.popup {
+            Popup::Slab => {
+                let tag = tag_label(&class_name);
+                let Tag::Label(label) = tag.clone() else {
+                    unreachable!()
+                };
+
+                let slab = Slab::new(
+                    self.factory.clone(),
+                    &self.title,
+                    tag,
+                    self.margins.clone(),
+                    self.anchors.clone(),
+                    self.duration,
+                )
+                .handle();
+
+                // Triggered by hand as well, since a repeated message doesn't change the label.
+                Box::new(move |value| {
+                    message_fn(value).apply_label(&label);
+                    slab.trigger();
+                })
+            }
+            Popup::Plate => {
+                let (factory, title) = (self.factory.clone(), self.title.clone());
+                let (margins, anchors, duration) =
+                    (self.margins.clone(), self.anchors.clone(), self.duration);
+
+                Box::new(move |value| {
+                    let tag = tag_label(&class_name);
+                    if let Tag::Label(label) = &tag {
+                        message_fn(value).apply_label(label);
+                    }
+
+                    Plate::new(
+                        factory.clone(),
+                        &title,
+                        tag,
+                        margins.clone(),
+                        anchors.clone(),
+                        duration,
+                    )
+                    .build();
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(300);
+
+    fn low_battery() -> Trigger {
+        Trigger::new(Threshold::below(10.0, "low"), 5.0, COOLDOWN)
+    }
+
+    #[test]
+    fn fires_once_while_the_value_stays_past_the_threshold() {
+        let (mut trigger, now) = (low_battery(), Instant::now());
+
+        assert!(!trigger.update(12.0, now));
+        assert!(trigger.update(9.0, now));
+        assert!(!trigger.update(8.0, now + COOLDOWN));
+        assert!(!trigger.update(7.0, now + COOLDOWN * 2));
+    }
+
+    #[test]
+    fn rearms_only_once_cleared_by_the_hysteresis() {
+        let (mut trigger, now) = (low_battery(), Instant::now());
+        let later = now + COOLDOWN;
+
+        assert!(trigger.update(9.0, now));
+
+        // Hovering around the threshold doesn't re-arm it.
+        assert!(!trigger.update(11.0, later));
+        assert!(!trigger.update(9.0, later));
+
+        assert!(!trigger.update(16.0, later));
+        assert!(trigger.update(9.0, later));
+    }
+
+    #[test]
+    fn respects_the_cooldown() {
+        let (mut trigger, now) = (low_battery(), Instant::now());
+        let soon = now + Duration::from_secs(60);
+
+        assert!(trigger.update(9.0, now));
+        assert!(!trigger.update(16.0, soon));
+
+        // Still armed, so it fires as soon as the cooldown is over.
+        assert!(!trigger.update(9.0, soon));
+        assert!(trigger.update(9.0, now + COOLDOWN));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::Variable;
+
+thread_local! {
+    static ENABLED: Variable<bool> = Variable::new(false);
+}
+
+/// Do Not Disturb mode, which keeps notification popups and Slabs from showing. Critical
+/// notifications still show, and every notification is still kept in the history.
+///
+/// The mode is shared by the whole application, and must be used from the GTK main thread.
+pub struct DoNotDisturb;
+
+impl DoNotDisturb {
+    /// Returns a `Variable` holding whether Do Not Disturb is on, e.g. to bind a bar icon to.
+    pub fn watch() -> Variable<bool> {
+        ENABLED.with(Variable::clone)
+    }
+
+    /// Returns true if Do Not Disturb is on.
+    pub fn is_enabled() -> bool {
+        ENABLED.with(Variable::get)
+    }
+
+    /// Turns Do Not Disturb on or off.
+    pub fn set(enabled: bool) {
+        ENABLED.with(|variable| variable.set(enabled));
+    }
+
+    /// Turns Do Not Disturb on if it is off, and off if it is on.
+    pub fn toggle() {
+        Self::set(!Self::is_enabled());
+    }
+}
@@ -1,24 +1,23 @@
-use std::{
-    rc::Rc,
-    sync::{Arc, Mutex},
-};
+use std::{future::Future, rc::Rc, sync::Mutex, time::Duration};
 
 use dbus::blocking::Connection;
 use gio::glib::ControlFlow;
 use gtk4::{
-    glib::timeout_add_seconds_local,
-    prelude::{BoxExt, ButtonExt},
-    Picture,
-};
-use networkmanager::{
-    devices::{Device, Wireless},
-    Error, NetworkManager,
+    glib::{timeout_add_seconds_local, Propagation},
+    prelude::{BoxExt, ButtonExt, WidgetExt},
+    EventControllerScroll, EventControllerScrollFlags, Picture,
 };
+use networkmanager::Error;
 use pulsectl::controllers::{DeviceControl, SinkController};
 use regex::Regex;
-use sysinfo::{DiskExt, System, SystemExt};
 
-use crate::widgets::Tag;
+use crate::{
+    scheduler::{schedule_mapped, Interval},
+    sources::{read_status, Storage},
+    widgets::Tag,
+    worker::{AsyncFetch, ThreadedFetch},
+    Text, Threshold,
+};
 
 #[derive(Clone, Copy)]
 pub struct RevealerState {
@@ -29,19 +28,13 @@ pub struct RevealerState {
 pub struct Internal;
 
 impl Internal {
-    /// Sets the static text of a Tag, using markup if the text contains HTML-like tags.
-    pub fn static_widget(tag: &Tag, text: &str) {
+    /// Sets the static text of a Tag. Plain text is never parsed as markup.
+    pub fn static_widget(tag: &Tag, text: impl Into<Text>) {
+        let text = text.into();
+
         match tag {
-            Tag::Label(label) => {
-                if text.contains("</") && text.contains('>') {
-                    label.set_markup(&text);
-                } else {
-                    label.set_text(&text);
-                }
-            }
-            Tag::Button(button) => {
-                button.set_label(&text);
-            }
+            Tag::Label(label) => text.apply_label(label),
+            Tag::Button(button) => text.apply_button(button),
             _ => (),
         }
     }
@@ -64,37 +57,36 @@ impl Internal {
         }
     }
 
+    /// Sets scroll behavior. The action receives the vertical scroll delta, which is negative
+    /// when scrolling up.
+    pub fn static_scroll(tag: &Tag, action: impl Fn(f64) + 'static) {
+        let controller = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL);
+
+        controller.connect_scroll(move |_, _dx, dy| {
+            action(dy);
+            Propagation::Stop
+        });
+
+        tag.widget().add_controller(controller);
+    }
+
+    /// Returns the signal strength of the connected access point as a bar.
+    /// For the full network status, updated as soon as it changes, use `sources::Network::watch`.
     pub fn get_network() -> Result<String, Error> {
         let dbus_conn = Connection::new_system()?;
-        let nm = NetworkManager::new(&dbus_conn);
-
-        for dev in nm.get_devices()? {
-            if let Device::WiFi(wifi) = dev {
-                let aps = wifi.get_all_access_points()?;
-
-                let mut max_strength = 0u8;
-                for ap in aps {
-                    let strength = ap.strength()?;
-                    if strength > max_strength {
-                        max_strength = strength;
-                    }
-                }
-
-                let wifi_status = match max_strength {
-                    0 => "░░░░░",
-                    1..=20 => "▂︎░░░░",
-                    21..=40 => "▂▃︎░░░",
-                    41..=60 => "▂▃▄︎░░",
-                    61..=80 => "▂▃▄▅︎░",
-                    81..=100 => "▂▃▄▅▆",
-                    _ => "X",
-                };
-
-                return Ok(wifi_status.to_string());
-            }
-        }
-
-        Ok("░░░░░".to_string())
+        let status = read_status(&dbus_conn)?;
+
+        let wifi_status = match status.strength.unwrap_or(0) {
+            0 => "░░░░░",
+            1..=20 => "▂︎░░░░",
+            21..=40 => "▂▃︎░░░",
+            41..=60 => "▂▃▄︎░░",
+            61..=80 => "▂▃▄▅︎░",
+            81..=100 => "▂▃▄▅▆",
+            _ => "X",
+        };
+
+        Ok(wifi_status.to_string())
     }
 
     /// Fetches the weather for a given location using the wttr.in API.
@@ -114,13 +106,14 @@ impl Internal {
         }
     }
 
-    /// Uses pulsectl-rs to return the formatted volume level.
+    /// Uses pulsectl-rs to return the formatted volume level of the default sink.
+    /// For a volume that updates as soon as it changes, use `sources::Sink::watch` instead.
     pub fn get_pactl_vol() -> String {
-        let mut handler = SinkController::create().unwrap();
-
-        let devices = handler.list_devices().expect("Failed to list devices");
+        let device = SinkController::create()
+            .ok()
+            .and_then(|mut handler| handler.get_default_device().ok());
 
-        if let Some(device) = devices.first() {
+        if let Some(device) = device {
             let vol = device.volume.to_string();
 
             vol.split_whitespace()
@@ -133,44 +126,34 @@ impl Internal {
     }
 
     /// Sets static text and then updates it at a given interval using a closure.
-    pub fn static_to_update<F, G>(tag: &Tag, format_fn: F, sleep: u32, updated_fn: G, interval: u32)
-    where
-        F: Fn() -> String + 'static,
-        G: Fn() -> String + 'static,
+    pub fn static_to_update<F, G, T, U>(
+        tag: &Tag,
+        format_fn: F,
+        sleep: u32,
+        updated_fn: G,
+        interval: impl Into<Interval>,
+    ) where
+        F: Fn() -> T + 'static,
+        G: Fn() -> U + 'static,
+        T: Into<Text>,
+        U: Into<Text>,
     {
         if let Tag::Label(label) = tag {
             let css_tag = label.clone();
             let css_updater = css_tag.clone();
-            let updated_fn = Arc::new(Mutex::new(updated_fn));
+            let updated_fn = Rc::new(updated_fn);
+            let widget = tag.widget();
 
-            let update = move || {
-                let text = format_fn();
-
-                if text.contains("</") && text.contains('>') {
-                    css_tag.set_markup(&text);
-                } else {
-                    css_tag.set_text(&text);
-                }
-
-                ControlFlow::Continue
-            };
+            format_fn().into().apply_label(&css_tag);
 
-            update();
+            let interval = interval.into();
 
             timeout_add_seconds_local(sleep, move || {
                 let css_updater = css_updater.clone();
-                let updated_fn = Arc::clone(&updated_fn);
-
-                timeout_add_seconds_local(interval, move || {
-                    let updated_text = (updated_fn.lock().unwrap())();
+                let updated_fn = Rc::clone(&updated_fn);
 
-                    if updated_text.contains("</") && updated_text.contains('>') {
-                        css_updater.set_markup(&updated_text);
-                    } else {
-                        css_updater.set_text(&updated_text);
-                    }
-
-                    ControlFlow::Continue
+                schedule_mapped(&widget, interval, move || {
+                    updated_fn().into().apply_label(&css_updater);
                 });
 
                 ControlFlow::Break
@@ -178,61 +161,160 @@ impl Internal {
         }
     }
 
-    /// Updates a GTK4 label using a closure at a given interval.
-    pub fn update_widget<F>(tag: &Tag, format_fn: F, interval: u32)
+    /// Updates a GTK4 label using a closure at a given interval. Updates are suspended while the
+    /// label is hidden, and resume as soon as it is shown again.
+    /// Closures returning a `String` are displayed as plain text; return a `Span` or
+    /// `Text::Markup` for markup.
+    pub fn update_widget<F, T>(tag: &Tag, format_fn: F, interval: impl Into<Interval>)
     where
-        F: Fn() -> String + 'static,
+        F: Fn() -> T + 'static,
+        T: Into<Text>,
     {
         if let Tag::Label(label) = tag {
             let css_tag = label.clone();
 
             let update = move || {
-                let text = format_fn();
+                format_fn().into().apply_label(&css_tag);
+            };
 
-                if text.contains("</") && text.contains('>') {
-                    css_tag.set_markup(&text);
-                } else {
-                    css_tag.set_text(&text);
-                }
+            update();
+
+            schedule_mapped(&tag.widget(), interval.into(), update);
+        }
+    }
 
-                ControlFlow::Continue
+    /// Updates a GTK4 label at a given interval, running the fetcher on a worker thread so that
+    /// blocking calls (D-Bus, disks, HTTP) don't freeze the UI. The result is then formatted on
+    /// the main thread. If a fetch is still running when the next one is due, that update is
+    /// skipped; if it takes longer than `timeout` seconds, its result is dropped.
+    pub fn update_widget_threaded<F, G, T, U>(
+        tag: &Tag,
+        fetch_fn: F,
+        format_fn: G,
+        interval: impl Into<Interval>,
+        timeout: u32,
+    ) where
+        F: Fn() -> T + Send + Sync + 'static,
+        G: Fn(T) -> U + 'static,
+        T: Send + 'static,
+        U: Into<Text>,
+    {
+        if let Tag::Label(label) = tag {
+            let css_tag = label.clone();
+            let fetch = ThreadedFetch::new(fetch_fn, Duration::from_secs(timeout.into()));
+            let format_fn = Rc::new(format_fn);
+
+            let update = move || {
+                let css_tag = css_tag.clone();
+                let format_fn = Rc::clone(&format_fn);
+
+                fetch.run(move |value| format_fn(value).into().apply_label(&css_tag));
             };
 
             update();
 
-            timeout_add_seconds_local(interval, update);
+            schedule_mapped(&tag.widget(), interval.into(), update);
         }
     }
 
-    /// Updates the time in a GTK4 label every second given a closure.
-    pub fn update_time<F>(tag: &Tag, format_fn: F)
+    /// Updates a GTK4 label at a given interval, running the fetcher as a future on the GLib
+    /// main context. Updates are skipped while the previous future is pending, and futures
+    /// taking longer than `timeout` seconds are cancelled.
+    pub fn update_widget_async<F, Fut, G, T, U>(
+        tag: &Tag,
+        fetch_fn: F,
+        format_fn: G,
+        interval: impl Into<Interval>,
+        timeout: u32,
+    ) where
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = T> + 'static,
+        G: Fn(T) -> U + 'static,
+        T: 'static,
+        U: Into<Text>,
+    {
+        if let Tag::Label(label) = tag {
+            let css_tag = label.clone();
+            let fetch = AsyncFetch::new(fetch_fn, Duration::from_secs(timeout.into()));
+            let format_fn = Rc::new(format_fn);
+
+            let update = move || {
+                let css_tag = css_tag.clone();
+                let format_fn = Rc::clone(&format_fn);
+
+                fetch.run(move |value| format_fn(value).into().apply_label(&css_tag));
+            };
+
+            update();
+
+            schedule_mapped(&tag.widget(), interval.into(), update);
+        }
+    }
+
+    /// Toggles the CSS classes of the given thresholds on a Tag, based on a single value.
+    pub fn static_thresholds(tag: &Tag, value: f64, thresholds: &[Threshold]) {
+        let widget = tag.widget();
+
+        for threshold in thresholds {
+            if threshold.matches(value) {
+                widget.add_css_class(threshold.class());
+            } else {
+                widget.remove_css_class(threshold.class());
+            }
+        }
+    }
+
+    /// Toggles the CSS classes of the given thresholds on a Tag using a closure at a given interval.
+    pub fn update_thresholds<F>(
+        tag: &Tag,
+        value_fn: F,
+        thresholds: Vec<Threshold>,
+        interval: impl Into<Interval>,
+    ) where
+        F: Fn() -> f64 + 'static,
+    {
+        let widget = tag.widget();
+        let tag = tag.clone();
+
+        let update = move || {
+            Internal::static_thresholds(&tag, value_fn(), &thresholds);
+        };
+
+        update();
+
+        schedule_mapped(&widget, interval.into(), update);
+    }
+
+    /// Updates the time in a GTK4 label exactly as each second of the system clock starts,
+    /// given a closure.
+    pub fn update_time<F, T>(tag: &Tag, format_fn: F)
     where
-        F: Fn() -> String + 'static,
+        F: Fn() -> T + 'static,
+        T: Into<Text>,
     {
-        Internal::update_widget(tag, format_fn, 1);
+        Internal::update_widget(tag, format_fn, Interval::SECOND);
     }
 
     /// Updates the storage in a GTK4 label every 2 minutes given a closure.
-    pub fn update_storage<F>(tag: &Tag, format_fn: F)
+    pub fn update_storage<F, T>(tag: &Tag, format_fn: F)
     where
-        F: Fn() -> String + 'static,
+        F: Fn() -> T + 'static,
+        T: Into<Text>,
     {
         Internal::update_widget(tag, format_fn, 120);
     }
 
-    /// Fetches the current storage usage as a percentage.
+    /// Fetches the current storage usage as a percentage, across every non-removable disk.
+    /// Returns 0 if there are none.
+    ///
+    /// This reads every disk on the calling thread, so a hung mount blocks it. Prefer
+    /// `sources::Storage::watch`, which reads on a worker thread and reports a `StorageError`
+    /// when nothing matches.
     pub fn get_storage() -> f64 {
-        let mut system = System::new_all();
-        system.refresh_disks();
-
-        let total_space: u64 = system.disks().iter().map(|disk| disk.total_space()).sum();
-        let available_space: u64 = system
-            .disks()
-            .iter()
-            .map(|disk| disk.available_space())
-            .sum();
-
-        ((total_space - available_space) as f64 / total_space as f64 * 100.0).round()
+        Storage::new()
+            .usage()
+            .map(|usage| usage.percent.round())
+            .unwrap_or(0.0)
     }
 
     /// Listens to the variable for changing the state of tag_reveal
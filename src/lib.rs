@@ -1,17 +1,33 @@
 #![allow(dead_code)]
 
+mod alert;
+mod do_not_disturb;
 mod factory;
 mod internal;
+mod notifier;
+mod scheduler;
+mod text;
+mod threshold;
+mod variable;
 mod wayland;
+mod worker;
 
 pub mod position;
+pub mod sources;
 pub mod taskbar;
 pub mod utils;
 pub mod widgets;
 
+pub use alert::{Alert, Popup};
+pub use do_not_disturb::DoNotDisturb;
 pub use factory::Factory;
 pub use internal::Internal;
 pub use internal::RevealerState;
+pub use notifier::Notifier;
+pub use scheduler::Interval;
+pub use text::{Span, Text};
+pub use threshold::Threshold;
+pub use variable::Variable;
 pub use wayland::Wayland;
 pub use widgets::builder::Builder;
 
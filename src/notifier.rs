@@ -0,0 +1,193 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use gtk4::{
+    prelude::{BoxExt, GtkWindowExt, WidgetExt},
+    Application, ApplicationWindow, Box, Orientation,
+};
+use gtk4_layer_shell::{Edge, Layer};
+
+use crate::{
+    sources::{Bus, Notification, Notifications, Urgency},
+    utils::notification_card,
+    widgets::{Animator, Stack, Transition},
+    Builder, DoNotDisturb, Wayland,
+};
+
+/// Shows notifications sent to the `org.freedesktop.Notifications` server, replacing mako or
+/// dunst.
+///
+/// Notifications are stacked as cards in an overlay window, newest first, which is hidden while
+/// there are none. Cards close when they expire, when their application closes them, or when
+/// they are dismissed, and are updated in place when a notification is replaced. While Do Not
+/// Disturb is on, only critical notifications are shown.
+pub struct Notifier {
+    factory: Application,
+    title: String,
+    margins: Vec<(Edge, i32)>,
+    anchors: Vec<(Edge, bool)>,
+    width: i32,
+    notifications: Notifications,
+    stack: Option<Stack>,
+    transition: Transition,
+}
+
+impl Notifier {
+    /// Creates a new `Notifier` serving notifications on the session bus.
+    pub fn new(
+        factory: Application,
+        title: &str,
+        margins: Vec<(Edge, i32)>,
+        anchors: Vec<(Edge, bool)>,
+    ) -> Self {
+        Self {
+            factory,
+            title: title.to_string(),
+            margins,
+            anchors,
+            width: 360,
+            notifications: Notifications::new(),
+            stack: None,
+            transition: Transition::none(),
+        }
+    }
+
+    /// Serves notifications on another bus, e.g. a private bus to test with `notify-send`.
+    pub fn on(mut self, bus: Bus) -> Self {
+        self.notifications = Notifications::on(bus);
+        self
+    }
+
+    /// Sets the width of the cards in pixels. Defaults to 360.
+    pub fn width(mut self, width: i32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Lines the notification window up with the other popups in a `Stack` while it is shown,
+    /// e.g. so OSDs show next to the notifications instead of over them.
+    pub fn stack(mut self, stack: &Stack) -> Self {
+        self.stack = Some(stack.clone());
+        self
+    }
+
+    /// Sets how the notification window animates as the first notification shows and the
+    /// last one closes. Defaults to no animation.
+    pub fn transition(mut self, transition: Transition) -> Self {
+        self.transition = transition;
+        self
+    }
+}
+
+impl Builder for Notifier {
+    /// Builds the notification window and starts the notification server.
+    ///
+    /// The cards are stacked in a box named "notifications".
+    fn build(self) {
+        let stack = Box::new(Orientation::Vertical, 8);
+
+        stack.set_widget_name("notifications");
+        stack.set_size_request(self.width, -1);
+
+        let window = ApplicationWindow::builder()
+            .application(&self.factory)
+            .title(self.title.clone())
+            .child(&stack)
+            .build();
+
+        if Wayland::detect_wayland() {
+            let wayland = Wayland::new(window.clone(), self.anchors, self.margins, Layer::Overlay);
+            wayland.setup_window()
+        }
+
+        window.set_decorated(false);
+        window.set_resizable(false);
+        window.hide();
+
+        Wayland::ipc_ignore_window(&self.title);
+
+        let (notifications, popups) = (self.notifications, self.stack);
+        let animator = Animator::new(&window, self.transition);
+        let cards: RefCell<HashMap<u32, (Notification, Box)>> = RefCell::new(HashMap::new());
+
+        let card = {
+            let notifications = notifications.clone();
+
+            move |notification: &Notification| {
+                let (invoker, dismisser, id) = (
+                    notifications.clone(),
+                    notifications.clone(),
+                    notification.id,
+                );
+
+                notification_card(
+                    notification,
+                    None,
+                    move |key| invoker.invoke(id, key),
+                    move || dismisser.dismiss(id),
+                )
+            }
+        };
+
+        // Only critical notifications show while Do Not Disturb is on.
+        let quiet = DoNotDisturb::watch();
+        let shown = notifications
+            .watch()
+            .combine(&quiet, |notifications, quiet| {
+                notifications
+                    .iter()
+                    .filter(|notification| !quiet || notification.urgency == Urgency::Critical)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            });
+
+        shown.subscribe(move |shown: &Vec<Notification>| {
+            let mut cards = cards.borrow_mut();
+
+            cards.retain(|id, (_, widget)| {
+                let up = shown.iter().any(|notification| notification.id == *id);
+                if !up {
+                    stack.remove(widget);
+                }
+                up
+            });
+
+            for notification in shown {
+                match cards.get_mut(&notification.id) {
+                    Some((current, _)) if current == notification => (),
+                    // A replaced notification keeps its place in the stack.
+                    Some((current, widget)) => {
+                        let replacement = card(notification);
+                        stack.insert_child_after(&replacement, Some(widget));
+                        stack.remove(widget);
+
+                        *current = notification.clone();
+                        *widget = replacement;
+                    }
+                    None => {
+                        let widget = card(notification);
+                        stack.prepend(&widget);
+
+                        cards.insert(notification.id, (notification.clone(), widget));
+                    }
+                }
+            }
+
+            if shown.is_empty() {
+                let popups = popups.clone();
+                animator.hide(move |window| {
+                    if let Some(popups) = &popups {
+                        popups.hide(window);
+                    }
+                });
+            } else {
+                // Shown again on every change, so the stack follows the window's size.
+                if let Some(popups) = &popups {
+                    let evicted = animator.clone();
+                    popups.show(&window, move || evicted.hide(|_| ()));
+                }
+
+                animator.show();
+            }
+        });
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use gio::glib::{
+    clone::Downgrade, timeout_add_local, timeout_add_local_once, timeout_add_seconds_local,
+    ControlFlow,
+};
+use gtk4::{prelude::WidgetExt, Widget};
+
+/// How often an updater fires.
+///
+/// Integers convert to `Interval::Seconds`, so existing intervals keep working as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interval {
+    /// Every n seconds. Cheapest, but may drift by up to a second, as GLib batches these timers.
+    Seconds(u32),
+    /// Every n milliseconds.
+    Millis(u32),
+    /// On every multiple of the given duration of the system clock, e.g. exactly as each
+    /// minute starts. Boundaries are counted from the Unix epoch.
+    Aligned(Duration),
+}
+
+impl Interval {
+    /// Fires exactly as each second of the system clock starts.
+    pub const SECOND: Self = Interval::Aligned(Duration::from_secs(1));
+
+    /// Fires exactly as each minute of the system clock starts.
+    pub const MINUTE: Self = Interval::Aligned(Duration::from_secs(60));
+
+    /// Returns the time left from `now`, counted from the Unix epoch, until the next boundary
+    /// of an aligned period.
+    fn until_boundary(period: Duration, now: Duration) -> Duration {
+        let period = period.as_millis().max(1);
+        let now = now.as_millis();
+
+        // One extra millisecond, so the clock has certainly ticked over when the timer fires.
+        Duration::from_millis((period - now % period) as u64 + 1)
+    }
+}
+
+impl From<u32> for Interval {
+    fn from(seconds: u32) -> Self {
+        Interval::Seconds(seconds)
+    }
+}
+
+type Task = Box<dyn Fn() -> ControlFlow>;
+type Group = Rc<RefCell<Vec<Task>>>;
+
+thread_local! {
+    static GROUPS: RefCell<HashMap<Interval, Group>> = RefCell::new(HashMap::new());
+}
+
+/// Runs a task at the given interval on the main loop, until it returns `ControlFlow::Break`.
+/// All tasks sharing an interval are coalesced into a single main loop timer.
+pub(crate) fn schedule<F>(interval: Interval, task: F)
+where
+    F: Fn() -> ControlFlow + 'static,
+{
+    GROUPS.with(|groups| {
+        let mut groups = groups.borrow_mut();
+
+        if let Some(group) = groups.get(&interval) {
+            group.borrow_mut().push(Box::new(task));
+            return;
+        }
+
+        let group: Group = Rc::new(RefCell::new(vec![Box::new(task)]));
+        groups.insert(interval, Rc::clone(&group));
+
+        match interval {
+            Interval::Seconds(seconds) => {
+                timeout_add_seconds_local(seconds, move || tick(interval, &group));
+            }
+            Interval::Millis(millis) => {
+                timeout_add_local(Duration::from_millis(millis.into()), move || {
+                    tick(interval, &group)
+                });
+            }
+            Interval::Aligned(period) => schedule_aligned(period, group),
+        }
+    });
+}
+
+/// Runs a task at the given interval while a widget is mapped. While the widget is unmapped
+/// (e.g. its window is hidden, or a `Revealer` is collapsed) the task is suspended, and any
+/// missed run happens as soon as the widget is mapped again.
+pub(crate) fn schedule_mapped<F>(widget: &Widget, interval: Interval, task: F)
+where
+    F: Fn() + 'static,
+{
+    let task = Rc::new(task);
+    let stale = Rc::new(Cell::new(false));
+
+    let (refresh, missed) = (Rc::clone(&task), Rc::clone(&stale));
+    widget.connect_map(move |_| {
+        if missed.replace(false) {
+            refresh();
+        }
+    });
+
+    let widget = widget.downgrade();
+    schedule(interval, move || {
+        let Some(widget) = widget.upgrade() else {
+            return ControlFlow::Break;
+        };
+
+        if widget.is_mapped() {
+            task();
+        } else {
+            stale.set(true);
+        }
+
+        ControlFlow::Continue
+    });
+}
+
+fn schedule_aligned(period: Duration, group: Group) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    timeout_add_local_once(Interval::until_boundary(period, now), move || {
+        if tick(Interval::Aligned(period), &group) == ControlFlow::Continue {
+            schedule_aligned(period, group);
+        }
+    });
+}
+
+/// Runs every task of a group, dropping the ones that are done. Removes the group (and stops
+/// its timer) once no tasks are left.
+fn tick(interval: Interval, group: &Group) -> ControlFlow {
+    // Tasks are taken out first, as they may schedule new tasks on the same interval.
+    let tasks = group.take();
+
+    let mut remaining: Vec<Task> = tasks
+        .into_iter()
+        .filter(|task| task() == ControlFlow::Continue)
+        .collect();
+
+    let mut pending = group.borrow_mut();
+    remaining.append(&mut pending);
+    *pending = remaining;
+
+    if pending.is_empty() {
+        GROUPS.with(|groups| groups.borrow_mut().remove(&interval));
+        ControlFlow::Break
+    } else {
+        ControlFlow::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_until_just_past_the_next_boundary() {
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(
+            Interval::until_boundary(minute, Duration::from_millis(60_000 * 5 + 15_000)),
+            Duration::from_millis(45_001)
+        );
+        assert_eq!(
+            Interval::until_boundary(minute, Duration::from_millis(60_000 * 5 + 59_999)),
+            Duration::from_millis(2)
+        );
+    }
+
+    #[test]
+    fn waits_a_full_period_when_on_a_boundary() {
+        assert_eq!(
+            Interval::until_boundary(Duration::from_secs(1), Duration::from_secs(42)),
+            Duration::from_millis(1_001)
+        );
+    }
+
+    #[test]
+    fn treats_sub_millisecond_periods_as_one_millisecond() {
+        assert_eq!(
+            Interval::until_boundary(Duration::from_micros(10), Duration::from_millis(7)),
+            Duration::from_millis(2)
+        );
+    }
+
+    #[test]
+    fn ticks_drop_finished_tasks_and_then_the_group() {
+        let interval = Interval::Millis(7);
+        let runs = Rc::new(Cell::new(0));
+        let group: Group = Rc::new(RefCell::new(Vec::new()));
+
+        for limit in [1, 2] {
+            let runs = Rc::clone(&runs);
+            let left = Cell::new(limit);
+
+            group.borrow_mut().push(Box::new(move || {
+                runs.set(runs.get() + 1);
+                left.set(left.get() - 1);
+
+                if left.get() == 0 {
+                    ControlFlow::Break
+                } else {
+                    ControlFlow::Continue
+                }
+            }));
+        }
+
+        GROUPS.with(|groups| groups.borrow_mut().insert(interval, Rc::clone(&group)));
+
+        assert_eq!(tick(interval, &group), ControlFlow::Continue);
+        assert_eq!(group.borrow().len(), 1);
+        assert_eq!(tick(interval, &group), ControlFlow::Break);
+        assert_eq!(runs.get(), 3);
+        assert!(GROUPS.with(|groups| groups.borrow().get(&interval).is_none()));
+    }
+
+    #[test]
+    fn ticks_keep_tasks_scheduled_while_running() {
+        let interval = Interval::Millis(11);
+        let group: Group = Rc::new(RefCell::new(Vec::new()));
+        let inner = Rc::clone(&group);
+
+        group.borrow_mut().push(Box::new(move || {
+            inner.borrow_mut().push(Box::new(|| ControlFlow::Continue));
+            ControlFlow::Break
+        }));
+
+        assert_eq!(tick(interval, &group), ControlFlow::Continue);
+        assert_eq!(group.borrow().len(), 1);
+    }
+}
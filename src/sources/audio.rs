@@ -0,0 +1,576 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use libpulse_binding::{
+    context::{subscribe::InterestMaskSet, State},
+    mainloop::standard::IterateResult,
+};
+use pulsectl::{
+    controllers::{
+        types::{ApplicationInfo, DeviceInfo},
+        AppControl, DeviceControl, SinkController, SourceController,
+    },
+    Handler,
+};
+
+use crate::Variable;
+
+/// A PulseAudio (or PipeWire-pulse) device, such as an output sink or an input source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioDevice {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    /// Average volume across all channels, in percent. Can exceed 100 when over-amplified.
+    pub volume: u32,
+    pub muted: bool,
+}
+
+impl AudioDevice {
+    fn from_info(info: &DeviceInfo) -> Self {
+        Self {
+            index: info.index,
+            name: info.name.clone().unwrap_or_default(),
+            description: info.description.clone().unwrap_or_default(),
+            volume: parse_volume(&info.volume.to_string()),
+            muted: info.mute,
+        }
+    }
+}
+
+/// An application playing audio (a PulseAudio sink input).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppStream {
+    pub index: u32,
+    /// Name of the application, falling back to the stream name.
+    pub name: String,
+    /// Icon name of the application, if it sets one.
+    pub icon: Option<String>,
+    /// Average volume across all channels, in percent.
+    pub volume: u32,
+    pub muted: bool,
+    /// Index of the sink the stream plays on.
+    pub sink: u32,
+}
+
+impl AppStream {
+    fn from_info(info: &ApplicationInfo) -> Self {
+        let name = info
+            .proplist
+            .get_str("application.name")
+            .or_else(|| info.name.clone())
+            .unwrap_or_default();
+
+        Self {
+            index: info.index,
+            name,
+            icon: info.proplist.get_str("application.icon_name"),
+            volume: parse_volume(&info.volume.to_string()),
+            muted: info.mute,
+            sink: info.connection_id,
+        }
+    }
+}
+
+/// Averages every percentage found in PulseAudio's channel volume string, e.g. `0: 100% 1: 98%`.
+fn parse_volume(volume: &str) -> u32 {
+    let percents: Vec<u32> = volume
+        .split_whitespace()
+        .filter_map(|s| s.trim_end_matches(',').strip_suffix('%'))
+        .filter_map(|s| s.parse().ok())
+        .collect();
+
+    if percents.is_empty() {
+        0
+    } else {
+        percents.iter().sum::<u32>() / percents.len() as u32
+    }
+}
+
+/// Returns the volume after stepping it by a number of percentage points. Stepping never pushes
+/// the volume past 100%, but doesn't pull an over-amplified volume back either.
+fn step(volume: u32, delta: i32) -> u32 {
+    let max = volume.max(100) as i64;
+
+    (volume as i64 + delta as i64).clamp(0, max) as u32
+}
+
+/// Returns the change in volume needed to go from one percentage to another, as a fraction.
+fn volume_delta(from: u32, to: u32) -> f64 {
+    (to as f64 - from as f64) / 100.0
+}
+
+/// Picks the default device out of a list by name.
+fn find_default(devices: &[AudioDevice], name: Option<&str>) -> Option<AudioDevice> {
+    let name = name?;
+
+    devices.iter().find(|device| device.name == name).cloned()
+}
+
+/// Returns the streams that still need to move over to a sink.
+fn streams_to_move(streams: &[AppStream], sink: u32) -> Vec<u32> {
+    streams
+        .iter()
+        .filter(|stream| stream.sink != sink)
+        .map(|stream| stream.index)
+        .collect()
+}
+
+/// Everything the PulseAudio thread reports back to the main thread.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PulseState {
+    sink: Option<AudioDevice>,
+    source: Option<AudioDevice>,
+    streams: Vec<AppStream>,
+    sinks: Vec<AudioDevice>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Sink,
+    Source,
+}
+
+enum Action {
+    SetVolume(u32),
+    StepVolume(i32),
+    ToggleMute,
+}
+
+enum Request {
+    Refresh,
+    /// The server came (back) up, so the controllers need a fresh connection.
+    Reconnect,
+    Device(Kind, Action),
+    Stream(u32, Action),
+    SetDefaultSink(String, bool),
+}
+
+/// How long to wait before reconnecting to PulseAudio at first, and at most.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// A single connection to PulseAudio, shared by every Tag on the main thread.
+///
+/// Controllers live on their own thread, which is woken by a subscription on a second
+/// connection whenever something changes, and by actions sent from the main thread.
+struct Pulse {
+    requests: Sender<Request>,
+    state: Variable<PulseState>,
+}
+
+thread_local! {
+    static PULSE: Pulse = Pulse::spawn();
+}
+
+impl Pulse {
+    fn spawn() -> Self {
+        let (requests, receiver) = channel::<Request>();
+        let (sender, states) = channel::<PulseState>();
+
+        thread::spawn(move || Self::serve(receiver, sender));
+
+        Self::subscribe(requests.clone());
+
+        Self {
+            requests,
+            state: Variable::listen(PulseState::default(), states),
+        }
+    }
+
+    fn send(request: Request) {
+        PULSE.with(|pulse| {
+            let _ = pulse.requests.send(request);
+        });
+    }
+
+    fn state() -> Variable<PulseState> {
+        PULSE.with(|pulse| pulse.state.clone())
+    }
+
+    /// Forwards change events to the PulseAudio thread. Whenever the server goes away (e.g. it
+    /// is restarted), reconnects with a growing delay.
+    fn subscribe(requests: Sender<Request>) {
+        thread::spawn(move || {
+            let mut delay = RECONNECT_DELAY;
+
+            loop {
+                if let Ok(handler) = Handler::connect("chunks-rs") {
+                    delay = RECONNECT_DELAY;
+
+                    if requests.send(Request::Reconnect).is_err()
+                        || !Self::forward_events(&handler, &requests)
+                    {
+                        break;
+                    }
+                }
+
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        });
+    }
+
+    /// Forwards events until the connection fails. Returns false once the main thread is gone.
+    fn forward_events(handler: &Handler, requests: &Sender<Request>) -> bool {
+        let closed = Rc::new(Cell::new(false));
+
+        let (events, done) = (requests.clone(), Rc::clone(&closed));
+        let mut context = handler.context.borrow_mut();
+        context.set_subscribe_callback(Some(Box::new(move |_, _, _| {
+            if events.send(Request::Refresh).is_err() {
+                done.set(true);
+            }
+        })));
+        context.subscribe(
+            InterestMaskSet::SINK
+                | InterestMaskSet::SOURCE
+                | InterestMaskSet::SINK_INPUT
+                | InterestMaskSet::SERVER,
+            |_| (),
+        );
+        drop(context);
+
+        loop {
+            let result = handler.mainloop.borrow_mut().iterate(true);
+
+            if closed.get() {
+                return false;
+            }
+
+            let failed = matches!(result, IterateResult::Quit(_) | IterateResult::Err(_))
+                || matches!(
+                    handler.context.borrow().get_state(),
+                    State::Failed | State::Terminated
+                );
+
+            if failed {
+                return true;
+            }
+        }
+    }
+
+    fn serve(receiver: Receiver<Request>, sender: Sender<PulseState>) {
+        let mut controllers = None;
+
+        while let Ok(request) = receiver.recv() {
+            // Events arrive in bursts, so handle everything queued before reporting back.
+            for request in std::iter::once(request).chain(receiver.try_iter()) {
+                if matches!(request, Request::Reconnect) {
+                    controllers = None;
+                }
+
+                if controllers.is_none() {
+                    controllers = SinkController::create()
+                        .and_then(|sinks| Ok((sinks, SourceController::create()?)))
+                        .ok();
+                }
+
+                let Some((sinks, sources)) = controllers.as_mut() else {
+                    continue;
+                };
+
+                match request {
+                    Request::Refresh | Request::Reconnect => (),
+                    Request::Device(Kind::Sink, action) => Self::handle(sinks, action),
+                    Request::Device(Kind::Source, action) => Self::handle(sources, action),
+                    Request::Stream(index, action) => Self::handle_stream(sinks, index, action),
+                    Request::SetDefaultSink(name, move_streams) => {
+                        Self::set_default_sink(sinks, &name, move_streams)
+                    }
+                }
+            }
+
+            let state = controllers
+                .as_mut()
+                .map(|(sinks, sources)| Self::read_state(sinks, sources))
+                .unwrap_or_default();
+
+            if sender.send(state).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn read_state(sinks: &mut SinkController, sources: &mut SourceController) -> PulseState {
+        let devices = sinks
+            .list_devices()
+            .map(|devices| {
+                devices
+                    .iter()
+                    .map(AudioDevice::from_info)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let server = sinks.get_server_info().ok();
+
+        PulseState {
+            sink: find_default(
+                &devices,
+                server
+                    .as_ref()
+                    .and_then(|info| info.default_sink_name.as_deref()),
+            ),
+            source: Self::default_device(sources),
+            streams: sinks
+                .list_applications()
+                .map(|apps| apps.iter().map(AppStream::from_info).collect())
+                .unwrap_or_default(),
+            sinks: devices,
+        }
+    }
+
+    fn default_device<C: DeviceControl<DeviceInfo>>(controller: &mut C) -> Option<AudioDevice> {
+        controller
+            .get_default_device()
+            .ok()
+            .map(|info| AudioDevice::from_info(&info))
+    }
+
+    fn handle<C: DeviceControl<DeviceInfo>>(controller: &mut C, action: Action) {
+        let Some(device) = Self::default_device(controller) else {
+            return;
+        };
+
+        match action {
+            Action::SetVolume(percent) => Self::set_volume(controller, &device, percent),
+            Action::StepVolume(delta) => {
+                Self::set_volume(controller, &device, step(device.volume, delta))
+            }
+            Action::ToggleMute => {
+                controller.set_device_mute_by_index(device.index, !device.muted);
+            }
+        }
+    }
+
+    fn handle_stream(sinks: &mut SinkController, index: u32, action: Action) {
+        let Ok(info) = sinks.get_app_by_index(index) else {
+            return;
+        };
+        let stream = AppStream::from_info(&info);
+
+        match action {
+            Action::SetVolume(percent) => Self::set_stream_volume(sinks, &stream, percent),
+            Action::StepVolume(delta) => {
+                Self::set_stream_volume(sinks, &stream, step(stream.volume, delta))
+            }
+            Action::ToggleMute => {
+                let _ = sinks.set_app_mute(stream.index, !stream.muted);
+            }
+        }
+    }
+
+    fn set_default_sink(sinks: &mut SinkController, name: &str, move_streams: bool) {
+        if !sinks.set_default_device(name).unwrap_or(false) || !move_streams {
+            return;
+        }
+
+        let (Ok(sink), Ok(apps)) = (sinks.get_device_by_name(name), sinks.list_applications())
+        else {
+            return;
+        };
+        let streams: Vec<AppStream> = apps.iter().map(AppStream::from_info).collect();
+
+        for index in streams_to_move(&streams, sink.index) {
+            let _ = sinks.move_app_by_name(index, name);
+        }
+    }
+
+    fn set_stream_volume(sinks: &mut SinkController, stream: &AppStream, percent: u32) {
+        let delta = volume_delta(stream.volume, percent);
+
+        if delta > 0.0 {
+            sinks.increase_app_volume_by_percent(stream.index, delta);
+        } else if delta < 0.0 {
+            sinks.decrease_app_volume_by_percent(stream.index, -delta);
+        }
+    }
+
+    fn set_volume<C: DeviceControl<DeviceInfo>>(
+        controller: &mut C,
+        device: &AudioDevice,
+        percent: u32,
+    ) {
+        let delta = volume_delta(device.volume, percent);
+
+        if delta > 0.0 {
+            controller.increase_device_volume_by_percent(device.index, delta);
+        } else if delta < 0.0 {
+            controller.decrease_device_volume_by_percent(device.index, -delta);
+        }
+    }
+}
+
+/// The default audio output.
+///
+/// All functions must be called from the GTK main thread.
+pub struct Sink;
+
+impl Sink {
+    /// Returns a `Variable` following the default sink, which updates as soon as its volume or
+    /// mute state changes, or another sink becomes the default.
+    /// It is `None` while PulseAudio is unreachable.
+    pub fn watch() -> Variable<Option<AudioDevice>> {
+        Pulse::state().map(|state| state.sink.clone())
+    }
+
+    /// Returns a `Variable` listing every available sink.
+    pub fn list() -> Variable<Vec<AudioDevice>> {
+        Pulse::state().map(|state| state.sinks.clone())
+    }
+
+    /// Makes the sink with the given name the default, optionally moving every playing stream
+    /// over to it as well.
+    pub fn set_default(name: &str, move_streams: bool) {
+        Pulse::send(Request::SetDefaultSink(name.to_string(), move_streams));
+    }
+
+    /// Sets the volume of the default sink, in percent.
+    pub fn set_volume(percent: u32) {
+        Pulse::send(Request::Device(Kind::Sink, Action::SetVolume(percent)));
+    }
+
+    /// Raises (or with a negative delta, lowers) the volume of the default sink by a number of
+    /// percentage points, without going past 100%.
+    pub fn step_volume(delta: i32) {
+        Pulse::send(Request::Device(Kind::Sink, Action::StepVolume(delta)));
+    }
+
+    /// Mutes or unmutes the default sink.
+    pub fn toggle_mute() {
+        Pulse::send(Request::Device(Kind::Sink, Action::ToggleMute));
+    }
+}
+
+/// The default audio input, usually a microphone.
+///
+/// All functions must be called from the GTK main thread.
+pub struct Source;
+
+impl Source {
+    /// Returns a `Variable` following the default source, which updates as soon as its volume or
+    /// mute state changes, or another source becomes the default.
+    /// It is `None` while PulseAudio is unreachable.
+    pub fn watch() -> Variable<Option<AudioDevice>> {
+        Pulse::state().map(|state| state.source.clone())
+    }
+
+    /// Sets the volume of the default source, in percent.
+    pub fn set_volume(percent: u32) {
+        Pulse::send(Request::Device(Kind::Source, Action::SetVolume(percent)));
+    }
+
+    /// Raises (or with a negative delta, lowers) the volume of the default source by a number of
+    /// percentage points, without going past 100%.
+    pub fn step_volume(delta: i32) {
+        Pulse::send(Request::Device(Kind::Source, Action::StepVolume(delta)));
+    }
+
+    /// Mutes or unmutes the default source.
+    pub fn toggle_mute() {
+        Pulse::send(Request::Device(Kind::Source, Action::ToggleMute));
+    }
+}
+
+/// Every application currently playing audio.
+///
+/// All functions must be called from the GTK main thread.
+pub struct Mixer;
+
+impl Mixer {
+    /// Returns a `Variable` listing every playing stream, which updates as soon as a stream
+    /// appears, disappears or changes.
+    pub fn watch() -> Variable<Vec<AppStream>> {
+        Pulse::state().map(|state| state.streams.clone())
+    }
+
+    /// Sets the volume of a stream, in percent.
+    pub fn set_volume(index: u32, percent: u32) {
+        Pulse::send(Request::Stream(index, Action::SetVolume(percent)));
+    }
+
+    /// Raises (or with a negative delta, lowers) the volume of a stream by a number of
+    /// percentage points, without going past 100%.
+    pub fn step_volume(index: u32, delta: i32) {
+        Pulse::send(Request::Stream(index, Action::StepVolume(delta)));
+    }
+
+    /// Mutes or unmutes a stream.
+    pub fn toggle_mute(index: u32) {
+        Pulse::send(Request::Stream(index, Action::ToggleMute));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(index: u32, name: &str) -> AudioDevice {
+        AudioDevice {
+            index,
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn stream(index: u32, sink: u32) -> AppStream {
+        AppStream {
+            index,
+            sink,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn averages_channel_volumes() {
+        assert_eq!(parse_volume("0: 100% 1: 98%"), 99);
+        assert_eq!(
+            parse_volume("front-left: 32768 /  50% / -18.06 dB,   front-right: 39321 /  60%"),
+            55
+        );
+        assert_eq!(parse_volume("mono: 98304 / 150% / 10.57 dB"), 150);
+        assert_eq!(parse_volume("(invalid)"), 0);
+    }
+
+    #[test]
+    fn steps_volume_within_bounds() {
+        assert_eq!(step(50, 5), 55);
+        assert_eq!(step(98, 5), 100);
+        assert_eq!(step(3, -5), 0);
+        // Over-amplified volumes can be lowered, but aren't raised any further.
+        assert_eq!(step(120, 5), 120);
+        assert_eq!(step(120, -5), 115);
+    }
+
+    #[test]
+    fn converts_percentages_to_volume_deltas() {
+        assert_eq!(volume_delta(40, 65), 0.25);
+        assert_eq!(volume_delta(65, 40), -0.25);
+        assert_eq!(volume_delta(70, 70), 0.0);
+    }
+
+    #[test]
+    fn finds_the_default_device_by_name() {
+        let devices = [device(1, "speakers"), device(2, "headphones")];
+
+        assert_eq!(
+            find_default(&devices, Some("headphones")),
+            Some(devices[1].clone())
+        );
+        assert_eq!(find_default(&devices, Some("hdmi")), None);
+        assert_eq!(find_default(&devices, None), None);
+    }
+
+    #[test]
+    fn moves_only_streams_on_other_sinks() {
+        let streams = [stream(10, 1), stream(11, 2), stream(12, 1)];
+
+        assert_eq!(streams_to_move(&streams, 1), [11]);
+        assert_eq!(streams_to_move(&streams, 3), [10, 11, 12]);
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, SendError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use gio::{
+    prelude::{FileExt, FileMonitorExt},
+    Cancellable, File, FileMonitor, FileMonitorFlags,
+};
+
+use crate::{sources::Bus, Variable};
+
+const LOGIND: &str = "org.freedesktop.login1";
+const SESSION: &str = "org.freedesktop.login1.Session";
+const SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a brightness that was just set counts over what sysfs reports, while logind is
+/// still applying it.
+const PENDING: Duration = Duration::from_millis(500);
+
+/// The brightness of a backlight, in the device's own raw units.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Brightness {
+    /// The backlight's name, e.g. "intel_backlight".
+    pub device: String,
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Brightness {
+    /// Brightness as a percentage of the maximum, from 0 to 100.
+    pub fn percent(&self) -> f64 {
+        if self.max == 0 {
+            0.0
+        } else {
+            self.current as f64 / self.max as f64 * 100.0
+        }
+    }
+
+    /// The raw value at a percentage of the maximum.
+    fn value_at(&self, percent: f64) -> u32 {
+        (self.max as f64 * percent.clamp(0.0, 100.0) / 100.0).round() as u32
+    }
+
+    /// The raw value a number of percentage points away, kept between 1% and 100%.
+    fn stepped(&self, delta: i32) -> u32 {
+        self.value_at((self.percent() + delta as f64).clamp(1.0, 100.0))
+            .max(1)
+    }
+}
+
+fn read_u32(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Picks the backlight to control, preferring firmware interfaces over platform and raw ones,
+/// the same way systemd does.
+fn default_device(root: &Path) -> Option<String> {
+    let mut devices: Vec<(u8, String)> = fs::read_dir(root.join("sys/class/backlight"))
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let rank = match fs::read_to_string(entry.path().join("type")) {
+                Ok(kind) if kind.trim() == "firmware" => 0,
+                Ok(kind) if kind.trim() == "platform" => 1,
+                _ => 2,
+            };
+
+            (rank, entry.file_name().to_string_lossy().to_string())
+        })
+        .collect();
+
+    devices.sort();
+    devices.into_iter().next().map(|(_, device)| device)
+}
+
+/// Sends brightness changes to logind one at a time, skipping any that are superseded while
+/// the previous call is running, e.g. when scrolling quickly. Stops if the bus can't be
+/// reached, so the next change starts over.
+fn serve(bus: Bus, receiver: Receiver<(String, u32)>) {
+    let conn = match bus.connect() {
+        Ok(conn) => conn,
+        Err(error) => {
+            eprintln!(
+                "chunks-rs: can't set the brightness, as the bus is unreachable: {}",
+                error
+            );
+            return;
+        }
+    };
+
+    let session = conn.with_proxy(LOGIND, SESSION_PATH, TIMEOUT);
+
+    while let Ok(request) = receiver.recv() {
+        let mut latest: HashMap<String, u32> = HashMap::new();
+
+        for (device, value) in std::iter::once(request).chain(receiver.try_iter()) {
+            latest.insert(device, value);
+        }
+
+        for (device, value) in latest {
+            let result: Result<(), dbus::Error> =
+                session.method_call(SESSION, "SetBrightness", ("backlight", &device, value));
+
+            if let Err(error) = result {
+                eprintln!(
+                    "chunks-rs: logind failed to set the brightness of {}: {}",
+                    device, error
+                );
+            }
+        }
+    }
+}
+
+/// A backlight's brightness, along with the monitor keeping it up to date.
+type Watcher = (Variable<Option<Brightness>>, FileMonitor);
+
+thread_local! {
+    static SETTERS: RefCell<HashMap<Bus, Sender<(String, u32)>>> = RefCell::new(HashMap::new());
+    static WATCHERS: RefCell<HashMap<PathBuf, Watcher>> = RefCell::new(HashMap::new());
+    static TARGETS: RefCell<HashMap<String, (u32, Instant)>> = RefCell::new(HashMap::new());
+}
+
+/// A screen backlight, read from `/sys/class/backlight` and set through logind, so no root
+/// or udev rule is needed.
+///
+/// Must be used from the GTK main thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlight {
+    root: PathBuf,
+    device: Option<String>,
+    bus: Bus,
+}
+
+impl Default for Backlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backlight {
+    /// Controls the preferred backlight through logind on the system bus.
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            device: None,
+            bus: Bus::System,
+        }
+    }
+
+    /// Controls the given backlight, e.g. "intel_backlight", instead of the preferred one.
+    pub fn device(mut self, device: &str) -> Self {
+        self.device = Some(device.to_string());
+        self
+    }
+
+    /// Reads `sys/class/backlight` relative to the given directory instead of `/`,
+    /// e.g. to test against fixture files.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Sets brightness through logind on the given bus, e.g. a mock on a private bus.
+    pub fn bus(mut self, bus: Bus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    fn device_path(&self) -> Option<(String, PathBuf)> {
+        let device = self.device.clone().or_else(|| default_device(&self.root))?;
+        let path = self.root.join("sys/class/backlight").join(&device);
+
+        Some((device, path))
+    }
+
+    /// Reads the brightness once. `None` without a backlight.
+    pub fn read(&self) -> Option<Brightness> {
+        let (device, path) = self.device_path()?;
+
+        Some(Brightness {
+            device,
+            current: read_u32(&path.join("brightness"))?,
+            max: read_u32(&path.join("max_brightness"))?,
+        })
+    }
+
+    /// Returns a `Variable` with the brightness, which updates as soon as the `brightness` file
+    /// changes rather than on an interval. `None` without a backlight.
+    pub fn watch(&self) -> Variable<Option<Brightness>> {
+        let Some((_, path)) = self.device_path() else {
+            return Variable::new(None);
+        };
+
+        WATCHERS.with(|watchers| {
+            if let Some((variable, _)) = watchers.borrow().get(&path) {
+                return variable.clone();
+            }
+
+            let variable = Variable::new(self.read());
+            let monitor = File::for_path(path.join("brightness"))
+                .monitor_file(FileMonitorFlags::NONE, None::<&Cancellable>);
+
+            if let Ok(monitor) = monitor {
+                let (backlight, updater) = (self.clone(), variable.clone());
+                monitor.connect_changed(move |_, _, _, _| updater.set(backlight.read()));
+
+                watchers
+                    .borrow_mut()
+                    .insert(path, (variable.clone(), monitor));
+            }
+
+            variable
+        })
+    }
+
+    /// Sets the brightness in raw units.
+    pub fn set(&self, value: u32) {
+        let Some((device, _)) = self.device_path() else {
+            return;
+        };
+
+        SETTERS.with(|setters| {
+            let mut setters = setters.borrow_mut();
+            let mut request = (device.clone(), value);
+
+            if let Some(sender) = setters.get(&self.bus) {
+                match sender.send(request) {
+                    Ok(()) => return,
+                    // The thread stopped, as the bus was unreachable, so try it again.
+                    Err(SendError(unsent)) => request = unsent,
+                }
+            }
+
+            let (sender, receiver) = channel::<(String, u32)>();
+            let bus = self.bus.clone();
+            thread::spawn(move || serve(bus, receiver));
+
+            let _ = sender.send(request);
+            setters.insert(self.bus.clone(), sender);
+        });
+
+        TARGETS.with(|targets| {
+            targets.borrow_mut().insert(device, (value, Instant::now()));
+        });
+    }
+
+    /// Reads the brightness, counting a value that was just set but may not have reached sysfs
+    /// yet, so repeated steps add up.
+    fn read_target(&self) -> Option<Brightness> {
+        let mut brightness = self.read()?;
+
+        TARGETS.with(|targets| {
+            if let Some((value, set)) = targets.borrow().get(&brightness.device) {
+                if set.elapsed() < PENDING {
+                    brightness.current = *value;
+                }
+            }
+        });
+
+        Some(brightness)
+    }
+
+    /// Sets the brightness as a percentage of the maximum.
+    pub fn set_percent(&self, percent: f64) {
+        if let Some(brightness) = self.read() {
+            self.set(brightness.value_at(percent));
+        }
+    }
+
+    /// Raises or lowers the brightness by a number of percentage points. Stepping down stops at
+    /// 1%, so the screen is never turned off by accident.
+    pub fn step(&self, delta: i32) {
+        if let Some(brightness) = self.read_target() {
+            self.set(brightness.stepped(delta));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbus::Message;
+
+    use super::*;
+    use crate::sources::fixture::{Fixture, MockService, TestBus};
+
+    fn brightness(current: u32, max: u32) -> Brightness {
+        Brightness {
+            device: "intel_backlight".to_string(),
+            current,
+            max,
+        }
+    }
+
+    /// A firmware and a raw backlight, plus a platform one without brightness files, under `/sys/class/backlight`.
+    const BACKLIGHT: &[(&str, &str)] = &[
+        ("sys/class/backlight/acpi_video0/type", "firmware\n"),
+        ("sys/class/backlight/acpi_video0/brightness", "5\n"),
+        ("sys/class/backlight/acpi_video0/max_brightness", "10\n"),
+        ("sys/class/backlight/intel_backlight/type", "raw\n"),
+        ("sys/class/backlight/intel_backlight/brightness", "4800\n"),
+        (
+            "sys/class/backlight/intel_backlight/max_brightness",
+            "96000\n",
+        ),
+        ("sys/class/backlight/nvidia_0/type", "platform\n"),
+    ];
+
+    #[test]
+    fn converts_brightness_to_percent() {
+        assert_eq!(brightness(4800, 96000).percent(), 5.0);
+        assert_eq!(brightness(0, 0).percent(), 0.0);
+        assert_eq!(brightness(0, 100).value_at(50.0), 50);
+        assert_eq!(brightness(0, 96000).value_at(150.0), 96000);
+        assert_eq!(brightness(0, 96000).value_at(-5.0), 0);
+    }
+
+    #[test]
+    fn steps_between_one_and_a_hundred_percent() {
+        assert_eq!(brightness(4800, 96000).stepped(5), 9600);
+        assert_eq!(brightness(4800, 96000).stepped(-10), 960);
+        assert_eq!(brightness(90000, 96000).stepped(10), 96000);
+        assert_eq!(brightness(1, 10).stepped(-5), 1);
+    }
+
+    #[test]
+    fn prefers_firmware_then_platform_then_raw_backlights() {
+        let fixture = Fixture::with_files("backlight-rank", BACKLIGHT);
+        assert_eq!(
+            default_device(fixture.path()),
+            Some("acpi_video0".to_string())
+        );
+
+        fs::remove_dir_all(fixture.path().join("sys/class/backlight/acpi_video0")).unwrap();
+        assert_eq!(default_device(fixture.path()), Some("nvidia_0".to_string()));
+
+        fs::remove_dir_all(fixture.path().join("sys/class/backlight/nvidia_0")).unwrap();
+        assert_eq!(
+            default_device(fixture.path()),
+            Some("intel_backlight".to_string())
+        );
+
+        assert_eq!(default_device(Path::new("/nonexistent")), None);
+    }
+
+    #[test]
+    fn reads_the_given_or_preferred_backlight() {
+        let fixture = Fixture::with_files("backlight-read", BACKLIGHT);
+        let backlight = Backlight::new().root(fixture.path());
+
+        assert_eq!(
+            backlight.read(),
+            Some(Brightness {
+                device: "acpi_video0".to_string(),
+                current: 5,
+                max: 10,
+            })
+        );
+        assert_eq!(
+            backlight.device("intel_backlight").read(),
+            Some(brightness(4800, 96000))
+        );
+        assert_eq!(Backlight::new().root("/nonexistent").read(), None);
+    }
+
+    #[test]
+    fn sets_brightness_through_logind() {
+        let test_bus = TestBus::start();
+        let (sender, receiver) = channel();
+        let _logind = MockService::start(&test_bus, LOGIND, Vec::new, move |message: &Message| {
+            if message.member().as_deref() != Some("SetBrightness") {
+                return None;
+            }
+
+            let _ = sender.send(message.read3::<String, String, u32>().ok()?);
+            Some(message.method_return())
+        });
+
+        let fixture = Fixture::with_files("backlight-set", BACKLIGHT);
+        Backlight::new()
+            .root(fixture.path())
+            .device("intel_backlight")
+            .bus(test_bus.bus())
+            .set_percent(50.0);
+
+        assert_eq!(
+            receiver.recv_timeout(TIMEOUT),
+            Ok((
+                "backlight".to_string(),
+                "intel_backlight".to_string(),
+                48000
+            ))
+        );
+    }
+
+    #[test]
+    fn tries_logind_again_after_failing_to_reach_the_bus() {
+        let fixture = Fixture::with_files("backlight-retry", BACKLIGHT);
+        let socket = fixture.path().join("bus");
+        let address = format!("unix:path={}", socket.display());
+        let backlight = Backlight::new()
+            .root(fixture.path())
+            .device("acpi_video0")
+            .bus(Bus::Address(address.clone()));
+
+        // Nothing listens on the bus yet, so this change is lost.
+        backlight.set(3);
+
+        let test_bus = TestBus::start_at(&address);
+        let (sender, receiver) = channel();
+        let _logind = MockService::start(&test_bus, LOGIND, Vec::new, move |message: &Message| {
+            let _ = sender.send(message.read3::<String, String, u32>().ok()?.2);
+            Some(message.method_return())
+        });
+
+        let started = Instant::now();
+        let mut set = None;
+
+        while set.is_none() && started.elapsed() < TIMEOUT {
+            backlight.set(7);
+            set = receiver.recv_timeout(Duration::from_millis(100)).ok();
+        }
+
+        assert_eq!(set, Some(7));
+    }
+}
@@ -0,0 +1,604 @@
+use std::{
+    convert::Infallible,
+    fs,
+    path::{Path as FsPath, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use dbus::{
+    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection},
+    message::{MatchRule, MessageType},
+    Path,
+};
+
+use crate::{
+    scheduler::Interval,
+    sources::{
+        bus::{Backend, Service},
+        Bus,
+    },
+    Variable,
+};
+
+const UPOWER: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const DEVICE: &str = "org.freedesktop.UPower.Device";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether a battery is charging.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BatteryState {
+    #[default]
+    Unknown,
+    Charging,
+    Discharging,
+    /// Plugged in, but held below full, e.g. by a charge threshold.
+    NotCharging,
+    Full,
+}
+
+impl BatteryState {
+    fn from_sysfs(status: &str) -> Self {
+        match status {
+            "Charging" => BatteryState::Charging,
+            "Discharging" => BatteryState::Discharging,
+            "Not charging" => BatteryState::NotCharging,
+            "Full" => BatteryState::Full,
+            _ => BatteryState::Unknown,
+        }
+    }
+
+    fn from_upower(state: u32) -> Self {
+        match state {
+            1 => BatteryState::Charging,
+            2 | 3 | 6 => BatteryState::Discharging,
+            4 => BatteryState::Full,
+            // Pending charge: plugged in, but not charging yet.
+            5 => BatteryState::NotCharging,
+            _ => BatteryState::Unknown,
+        }
+    }
+}
+
+/// A single battery.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatteryInfo {
+    /// The battery's name, e.g. "BAT0".
+    pub name: String,
+    /// Charge, in percent.
+    pub capacity: f64,
+    pub state: BatteryState,
+    /// Energy left, in watt-hours.
+    pub energy: Option<f64>,
+    /// Energy when full, in watt-hours.
+    pub energy_full: Option<f64>,
+    /// Power drawn or charged at, in watts.
+    pub power: Option<f64>,
+    pub time_to_empty: Option<Duration>,
+    pub time_to_full: Option<Duration>,
+}
+
+/// Every battery in the system, along with whether it is plugged in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatteryStatus {
+    pub batteries: Vec<BatteryInfo>,
+    /// Whether AC power is connected.
+    pub plugged: bool,
+}
+
+impl BatteryStatus {
+    /// Charge across every battery in percent, weighted by their size when it is known.
+    /// `None` without batteries.
+    pub fn capacity(&self) -> Option<f64> {
+        if self.batteries.is_empty() {
+            return None;
+        }
+
+        let energy: Option<f64> = self.batteries.iter().map(|battery| battery.energy).sum();
+        let full: Option<f64> = self
+            .batteries
+            .iter()
+            .map(|battery| battery.energy_full)
+            .sum();
+
+        match (energy, full) {
+            (Some(energy), Some(full)) if full > 0.0 => Some(energy / full * 100.0),
+            _ => Some(
+                self.batteries
+                    .iter()
+                    .map(|battery| battery.capacity)
+                    .sum::<f64>()
+                    / self.batteries.len() as f64,
+            ),
+        }
+    }
+
+    /// State across every battery: charging if any is, otherwise discharging if any is,
+    /// otherwise full if all are.
+    pub fn state(&self) -> BatteryState {
+        let any = |state| self.batteries.iter().any(|battery| battery.state == state);
+
+        if any(BatteryState::Charging) {
+            BatteryState::Charging
+        } else if any(BatteryState::Discharging) {
+            BatteryState::Discharging
+        } else if !self.batteries.is_empty()
+            && self
+                .batteries
+                .iter()
+                .all(|battery| battery.state == BatteryState::Full)
+        {
+            BatteryState::Full
+        } else if any(BatteryState::NotCharging) {
+            BatteryState::NotCharging
+        } else {
+            BatteryState::Unknown
+        }
+    }
+
+    /// Combined power draw across every battery, in watts.
+    pub fn power(&self) -> Option<f64> {
+        self.batteries
+            .iter()
+            .filter_map(|battery| battery.power)
+            .reduce(|a, b| a + b)
+    }
+
+    /// Time until every battery is empty, while discharging.
+    pub fn time_to_empty(&self) -> Option<Duration> {
+        self.batteries
+            .iter()
+            .filter_map(|battery| battery.time_to_empty)
+            .reduce(|a, b| a + b)
+    }
+
+    /// Time until every battery is full, while charging. Batteries charge in parallel, so this
+    /// is the longest of them.
+    pub fn time_to_full(&self) -> Option<Duration> {
+        self.batteries
+            .iter()
+            .filter_map(|battery| battery.time_to_full)
+            .max()
+    }
+}
+
+/// A list of icons for increasing charge levels, e.g. Nerd Font glyphs or icon names, with
+/// optional separate lists for charging and an icon for a full battery.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IconRamp {
+    levels: Vec<String>,
+    charging: Vec<String>,
+    full: Option<String>,
+}
+
+impl IconRamp {
+    /// Creates a ramp from icons for increasing charge levels, each covering an equal share.
+    pub fn new(levels: &[&str]) -> Self {
+        Self {
+            levels: levels.iter().map(|icon| icon.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Uses a separate list of icons while charging.
+    pub fn charging(mut self, levels: &[&str]) -> Self {
+        self.charging = levels.iter().map(|icon| icon.to_string()).collect();
+        self
+    }
+
+    /// Uses a separate icon once the battery is full.
+    pub fn full(mut self, icon: &str) -> Self {
+        self.full = Some(icon.to_string());
+        self
+    }
+
+    /// The symbolic battery icons of freedesktop icon themes, in steps of 10%.
+    pub fn symbolic() -> Self {
+        let levels: Vec<String> = (0..=100)
+            .step_by(10)
+            .map(|level| format!("battery-level-{}-symbolic", level))
+            .collect();
+        let charging: Vec<String> = (0..=100)
+            .step_by(10)
+            .map(|level| format!("battery-level-{}-charging-symbolic", level))
+            .collect();
+
+        Self {
+            levels,
+            charging,
+            full: Some("battery-full-charged-symbolic".to_string()),
+        }
+    }
+
+    /// Returns the icon for a charge level and state, or an empty string if the ramp is empty.
+    pub fn icon(&self, capacity: f64, state: BatteryState) -> &str {
+        if state == BatteryState::Full {
+            if let Some(full) = &self.full {
+                return full;
+            }
+        }
+
+        let levels = if state == BatteryState::Charging && !self.charging.is_empty() {
+            &self.charging
+        } else {
+            &self.levels
+        };
+
+        if levels.is_empty() {
+            return "";
+        }
+
+        let step = (capacity.clamp(0.0, 100.0) / 100.0 * levels.len() as f64) as usize;
+
+        &levels[step.min(levels.len() - 1)]
+    }
+
+    /// Returns the icon for a `BatteryStatus`.
+    pub fn status_icon(&self, status: &BatteryStatus) -> &str {
+        self.icon(status.capacity().unwrap_or(0.0), status.state())
+    }
+}
+
+fn read_value(path: &FsPath, name: &str) -> Option<String> {
+    fs::read_to_string(path.join(name))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+/// Reads a value in micro-units, e.g. µW or µWh.
+fn read_micro(path: &FsPath, name: &str) -> Option<f64> {
+    read_value(path, name)?
+        .parse::<f64>()
+        .ok()
+        .map(|value| value / 1_000_000.0)
+}
+
+fn read_battery(path: &FsPath, name: String) -> BatteryInfo {
+    let voltage = read_micro(path, "voltage_now");
+
+    // Batteries report either energy (µWh, µW) or charge (µAh, µA), which needs the voltage.
+    let energy =
+        read_micro(path, "energy_now").or_else(|| Some(read_micro(path, "charge_now")? * voltage?));
+    let energy_full = read_micro(path, "energy_full")
+        .or_else(|| Some(read_micro(path, "charge_full")? * voltage?));
+    let power = read_micro(path, "power_now")
+        .or_else(|| Some(read_micro(path, "current_now")? * voltage?))
+        .map(f64::abs);
+
+    let state = BatteryState::from_sysfs(&read_value(path, "status").unwrap_or_default());
+
+    let capacity = read_value(path, "capacity")
+        .and_then(|capacity| capacity.parse().ok())
+        .or_else(|| match (energy, energy_full) {
+            (Some(energy), Some(full)) if full > 0.0 => Some(energy / full * 100.0),
+            _ => None,
+        })
+        .unwrap_or(0.0);
+
+    // Drivers may report more energy than the full charge while topping off, which must not
+    // make for a negative Duration.
+    let hours = |energy: f64| {
+        power
+            .filter(|power| *power > 0.0)
+            .and_then(|power| Duration::try_from_secs_f64(energy.max(0.0) / power * 3600.0).ok())
+    };
+
+    BatteryInfo {
+        name,
+        capacity,
+        state,
+        energy,
+        energy_full,
+        power,
+        time_to_empty: (state == BatteryState::Discharging)
+            .then(|| hours(energy?))
+            .flatten(),
+        time_to_full: (state == BatteryState::Charging)
+            .then(|| hours(energy_full? - energy?))
+            .flatten(),
+    }
+}
+
+fn read_sysfs(root: &FsPath) -> BatteryStatus {
+    let mut status = BatteryStatus::default();
+
+    let Ok(entries) = fs::read_dir(root.join("sys/class/power_supply")) else {
+        return status;
+    };
+
+    let mut supplies: Vec<(String, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            )
+        })
+        .collect();
+    supplies.sort();
+
+    for (name, path) in supplies {
+        match read_value(&path, "type").as_deref() {
+            Some("Mains") | Some("USB") => {
+                status.plugged |= read_value(&path, "online").as_deref() == Some("1");
+            }
+            // Peripherals such as mice and headsets report a scope of "Device".
+            Some("Battery") if read_value(&path, "scope").as_deref() != Some("Device") => {
+                status.batteries.push(read_battery(&path, name));
+            }
+            _ => (),
+        }
+    }
+
+    status
+}
+
+fn read_upower(conn: &Connection) -> Result<BatteryStatus, dbus::Error> {
+    let upower = conn.with_proxy(UPOWER, UPOWER_PATH, TIMEOUT);
+
+    let (devices,): (Vec<Path<'static>>,) = upower.method_call(UPOWER, "EnumerateDevices", ())?;
+
+    let mut status = BatteryStatus {
+        plugged: !upower.get::<bool>(UPOWER, "OnBattery").unwrap_or(false),
+        ..Default::default()
+    };
+
+    for device in devices {
+        let device = conn.with_proxy(UPOWER, &device, TIMEOUT);
+
+        // Type 2 is a battery; PowerSupply is false for peripherals.
+        if device.get::<u32>(DEVICE, "Type").ok() != Some(2)
+            || !device.get::<bool>(DEVICE, "PowerSupply").unwrap_or(false)
+        {
+            continue;
+        }
+
+        let seconds = |property| {
+            device
+                .get::<i64>(DEVICE, property)
+                .ok()
+                .filter(|seconds| *seconds > 0)
+                .map(|seconds| Duration::from_secs(seconds as u64))
+        };
+
+        status.batteries.push(BatteryInfo {
+            name: device.get(DEVICE, "NativePath").unwrap_or_default(),
+            capacity: device.get(DEVICE, "Percentage").unwrap_or(0.0),
+            state: BatteryState::from_upower(device.get(DEVICE, "State").unwrap_or(0)),
+            energy: device.get(DEVICE, "Energy").ok(),
+            energy_full: device.get(DEVICE, "EnergyFull").ok(),
+            power: device.get(DEVICE, "EnergyRate").ok(),
+            time_to_empty: seconds("TimeToEmpty"),
+            time_to_full: seconds("TimeToFull"),
+        });
+    }
+
+    Ok(status)
+}
+
+/// UPower, read again whenever it emits a signal.
+struct UPower;
+
+impl Service for UPower {
+    type State = BatteryStatus;
+    type Request = Infallible;
+
+    fn connect(_: &Connection, _: &Arc<AtomicBool>) -> Option<Self> {
+        Some(UPower)
+    }
+
+    fn rules(&self) -> Vec<MatchRule<'static>> {
+        vec![MatchRule::new()
+            .with_type(MessageType::Signal)
+            .with_sender(UPOWER)]
+    }
+
+    fn read(&mut self, conn: &Connection, _: &BatteryStatus) -> BatteryStatus {
+        read_upower(conn).unwrap_or_default()
+    }
+}
+
+/// The system's batteries, read from sysfs or UPower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Battery {
+    root: PathBuf,
+}
+
+impl Default for Battery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Battery {
+    /// Reads `/sys/class/power_supply`.
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+        }
+    }
+
+    /// Reads `sys/class/power_supply` relative to the given directory instead of `/`,
+    /// e.g. to test against fixture files.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Reads the status of every battery once.
+    pub fn read(&self) -> BatteryStatus {
+        read_sysfs(&self.root)
+    }
+
+    /// Returns a `Variable` with the status of every battery, read from sysfs at a given
+    /// interval. Subscribers are only notified when something changed.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(self, interval: impl Into<Interval>) -> Variable<BatteryStatus> {
+        Variable::poll(interval, move || self.read())
+    }
+
+    /// Returns a `Variable` following UPower on the system bus. It updates as soon as UPower
+    /// reports a change, rather than on an interval.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch_upower() -> Variable<BatteryStatus> {
+        Battery::watch_upower_on(Bus::System)
+    }
+
+    /// Same as `watch_upower`, but for a UPower on the given bus.
+    pub fn watch_upower_on(bus: Bus) -> Variable<BatteryStatus> {
+        Backend::<UPower>::on(&bus).state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::fixture::Fixture;
+
+    /// Two batteries, a wireless mouse and two chargers, under `/sys/class/power_supply`.
+    const POWER_SUPPLY: &[(&str, &str)] = &[
+        ("sys/class/power_supply/AC/type", "Mains\n"),
+        ("sys/class/power_supply/AC/online", "1\n"),
+        ("sys/class/power_supply/BAT0/type", "Battery\n"),
+        ("sys/class/power_supply/BAT0/status", "Discharging\n"),
+        ("sys/class/power_supply/BAT0/capacity", "50\n"),
+        ("sys/class/power_supply/BAT0/energy_now", "25000000\n"),
+        ("sys/class/power_supply/BAT0/energy_full", "50000000\n"),
+        ("sys/class/power_supply/BAT0/power_now", "10000000\n"),
+        ("sys/class/power_supply/BAT1/type", "Battery\n"),
+        ("sys/class/power_supply/BAT1/status", "Charging\n"),
+        ("sys/class/power_supply/BAT1/charge_now", "2000000\n"),
+        ("sys/class/power_supply/BAT1/charge_full", "4000000\n"),
+        ("sys/class/power_supply/BAT1/voltage_now", "12000000\n"),
+        ("sys/class/power_supply/BAT1/current_now", "-1000000\n"),
+        ("sys/class/power_supply/hidpp_battery_0/type", "Battery\n"),
+        ("sys/class/power_supply/hidpp_battery_0/scope", "Device\n"),
+        ("sys/class/power_supply/hidpp_battery_0/capacity", "90\n"),
+        ("sys/class/power_supply/ucsi-source-psy-1/type", "USB\n"),
+        ("sys/class/power_supply/ucsi-source-psy-1/online", "0\n"),
+    ];
+
+    #[test]
+    fn reads_energy_based_batteries() {
+        let fixture = Fixture::with_files("battery-energy", POWER_SUPPLY);
+        let status = Battery::new().root(fixture.path()).read();
+
+        assert_eq!(
+            status.batteries[0],
+            BatteryInfo {
+                name: "BAT0".to_string(),
+                capacity: 50.0,
+                state: BatteryState::Discharging,
+                energy: Some(25.0),
+                energy_full: Some(50.0),
+                power: Some(10.0),
+                time_to_empty: Some(Duration::from_secs(9000)),
+                time_to_full: None,
+            }
+        );
+    }
+
+    #[test]
+    fn reads_charge_based_batteries_through_their_voltage() {
+        let fixture = Fixture::with_files("battery-charge", POWER_SUPPLY);
+        let status = Battery::new().root(fixture.path()).read();
+
+        assert_eq!(
+            status.batteries[1],
+            BatteryInfo {
+                name: "BAT1".to_string(),
+                capacity: 50.0,
+                state: BatteryState::Charging,
+                energy: Some(24.0),
+                energy_full: Some(48.0),
+                power: Some(12.0),
+                time_to_empty: None,
+                time_to_full: Some(Duration::from_secs(7200)),
+            }
+        );
+    }
+
+    #[test]
+    fn reads_batteries_charged_past_full_without_panicking() {
+        let fixture = Fixture::with_files("battery-overcharged", POWER_SUPPLY);
+        fixture
+            .file("sys/class/power_supply/BAT0/status", "Charging\n")
+            .file("sys/class/power_supply/BAT0/energy_now", "50400000\n")
+            .file("sys/class/power_supply/BAT0/power_now", "1\n");
+        let status = Battery::new().root(fixture.path()).read();
+
+        assert_eq!(status.batteries[0].time_to_full, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn skips_peripherals_and_combines_batteries() {
+        let fixture = Fixture::with_files("battery-status", POWER_SUPPLY);
+        let status = Battery::new().root(fixture.path()).read();
+
+        assert!(status.plugged);
+        assert_eq!(status.batteries.len(), 2);
+        assert_eq!(status.capacity(), Some(50.0));
+        assert_eq!(status.state(), BatteryState::Charging);
+        assert_eq!(status.power(), Some(22.0));
+        assert_eq!(status.time_to_empty(), Some(Duration::from_secs(9000)));
+        assert_eq!(status.time_to_full(), Some(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn reads_nothing_without_power_supplies() {
+        let fixture = Fixture::new("battery-empty");
+        let status = Battery::new().root(fixture.path()).read();
+
+        assert_eq!(status, BatteryStatus::default());
+        assert_eq!(status.capacity(), None);
+        assert_eq!(status.state(), BatteryState::Unknown);
+    }
+
+    #[test]
+    fn maps_states() {
+        assert_eq!(BatteryState::from_sysfs("Charging"), BatteryState::Charging);
+        assert_eq!(
+            BatteryState::from_sysfs("Not charging"),
+            BatteryState::NotCharging
+        );
+        assert_eq!(BatteryState::from_sysfs("Unknown"), BatteryState::Unknown);
+
+        assert_eq!(BatteryState::from_upower(0), BatteryState::Unknown);
+        assert_eq!(BatteryState::from_upower(1), BatteryState::Charging);
+        assert_eq!(BatteryState::from_upower(2), BatteryState::Discharging);
+        assert_eq!(BatteryState::from_upower(3), BatteryState::Discharging);
+        assert_eq!(BatteryState::from_upower(4), BatteryState::Full);
+        assert_eq!(BatteryState::from_upower(5), BatteryState::NotCharging);
+        assert_eq!(BatteryState::from_upower(6), BatteryState::Discharging);
+    }
+
+    #[test]
+    fn picks_icons_by_level_and_state() {
+        let ramp = IconRamp::new(&["empty", "low", "high", "most"])
+            .charging(&["charging-low", "charging-high"])
+            .full("full");
+
+        assert_eq!(ramp.icon(0.0, BatteryState::Discharging), "empty");
+        assert_eq!(ramp.icon(24.9, BatteryState::Discharging), "empty");
+        assert_eq!(ramp.icon(25.0, BatteryState::Discharging), "low");
+        assert_eq!(ramp.icon(100.0, BatteryState::Discharging), "most");
+        assert_eq!(ramp.icon(150.0, BatteryState::Unknown), "most");
+        assert_eq!(ramp.icon(60.0, BatteryState::Charging), "charging-high");
+        assert_eq!(ramp.icon(60.0, BatteryState::NotCharging), "high");
+        assert_eq!(ramp.icon(100.0, BatteryState::Full), "full");
+
+        assert_eq!(IconRamp::default().icon(50.0, BatteryState::Charging), "");
+        assert_eq!(
+            IconRamp::symbolic().icon(0.0, BatteryState::Charging),
+            "battery-level-0-charging-symbolic"
+        );
+
+        let fixture = Fixture::with_files("battery-icon", POWER_SUPPLY);
+        let status = Battery::new().root(fixture.path()).read();
+
+        assert_eq!(ramp.status_icon(&status), "charging-high");
+    }
+}
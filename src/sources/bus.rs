@@ -0,0 +1,314 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use dbus::{
+    blocking::Connection,
+    channel::{Channel, MatchingReceiver},
+    message::MatchRule,
+    Error, Message,
+};
+
+use crate::Variable;
+
+/// The D-Bus bus a data source connects to.
+///
+/// Sources default to the bus their service normally lives on, but can be pointed at any bus
+/// address instead, e.g. a private `dbus-daemon` running a mock service.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Bus {
+    System,
+    Session,
+    /// A bus address, e.g. `unix:path=/tmp/test-bus`.
+    Address(String),
+}
+
+impl Bus {
+    /// Opens a new connection to the bus.
+    pub(crate) fn connect(&self) -> Result<Connection, Error> {
+        match self {
+            Bus::System => Connection::new_system(),
+            Bus::Session => Connection::new_session(),
+            Bus::Address(address) => {
+                let mut channel = Channel::open_private(address)?;
+                channel.register()?;
+
+                Ok(Connection::from(channel))
+            }
+        }
+    }
+}
+
+/// A service a backend follows on a bus, from a thread of its own: it is read once, then again
+/// whenever one of its signals arrives, and requests from the main thread are handled in
+/// between.
+pub(crate) trait Service: Sized + 'static {
+    type State: Clone + Default + PartialEq + Send + 'static;
+    type Request: Send + 'static;
+
+    /// How long to wait for a signal or request before checking whether the service is `stale`.
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Sets the service up once connected. Setting `changed` reads the service again. Returns
+    /// `None` if the service can't be followed on this bus, which stops the backend.
+    fn connect(conn: &Connection, changed: &Arc<AtomicBool>) -> Option<Self>;
+
+    /// The signals that mean the service changed.
+    fn rules(&self) -> Vec<MatchRule<'static>>;
+
+    /// Whether a matching signal is worth reading the service again for.
+    fn relevant(_message: &Message) -> bool {
+        true
+    }
+
+    /// Reads the service, given what it was last read as.
+    fn read(&mut self, conn: &Connection, previous: &Self::State) -> Self::State;
+
+    fn handle(&mut self, _conn: &Connection, _state: &Self::State, _request: Self::Request) {}
+
+    /// Whether the service should be read again without a signal, some time after it last was.
+    fn stale(&self, _state: &Self::State, _since: Duration) -> bool {
+        false
+    }
+
+    /// How long to wait this time, for services with deadlines shorter than `INTERVAL`.
+    fn interval(&self) -> Duration {
+        Self::INTERVAL
+    }
+}
+
+/// The interface of the calls that wake a backend's thread up to handle requests.
+const WAKE: &str = "io.github.drkrssll.Chunks.Wake";
+
+/// Forwards requests to a backend's thread, waking it up with a call on a connection of its
+/// own, as the thread otherwise sleeps until a signal arrives.
+fn forward<R: Send>(bus: Bus, destination: String, requests: Receiver<R>, queue: Sender<R>) {
+    let Ok(conn) = bus.connect() else {
+        return;
+    };
+
+    while let Ok(request) = requests.recv() {
+        if queue.send(request).is_err() {
+            break;
+        }
+
+        let Ok(mut wake) = Message::new_method_call(&destination, "/", WAKE, "Wake") else {
+            break;
+        };
+        wake.set_no_reply(true);
+
+        if conn.channel().send(wake).is_err() {
+            break;
+        }
+        conn.channel().flush();
+    }
+}
+
+fn serve<S: Service>(bus: Bus, requests: Receiver<S::Request>, sender: Sender<S::State>) {
+    let Ok(conn) = bus.connect() else {
+        let _ = sender.send(S::State::default());
+        return;
+    };
+
+    let changed = Arc::new(AtomicBool::new(true));
+    let Some(mut service) = S::connect(&conn, &changed) else {
+        let _ = sender.send(S::State::default());
+        return;
+    };
+
+    let (queue, requests_queued) = channel();
+    let destination = conn.unique_name().to_string();
+    thread::spawn(move || forward(bus, destination, requests, queue));
+
+    conn.start_receive(
+        MatchRule::new_method_call().with_interface(WAKE),
+        Box::new(|_, _| true),
+    );
+
+    for rule in service.rules() {
+        if conn.add_match_no_cb(&rule.match_str()).is_err() {
+            return;
+        }
+
+        let flag = Arc::clone(&changed);
+        conn.start_receive(
+            rule,
+            Box::new(move |message, _| {
+                if S::relevant(&message) {
+                    flag.store(true, Ordering::Release);
+                }
+                true
+            }),
+        );
+    }
+
+    let mut state = S::State::default();
+    let mut last_read = Instant::now();
+
+    loop {
+        for request in requests_queued.try_iter() {
+            service.handle(&conn, &state, request);
+        }
+
+        if changed.swap(false, Ordering::AcqRel) || service.stale(&state, last_read.elapsed()) {
+            state = service.read(&conn, &state);
+            last_read = Instant::now();
+
+            if sender.send(state.clone()).is_err() {
+                break;
+            }
+        }
+
+        if conn.process(service.interval()).is_err() {
+            let _ = sender.send(S::State::default());
+            break;
+        }
+
+        // Signals arrive in bursts, so handle everything queued before reading the service.
+        while let Ok(true) = conn.process(Duration::ZERO) {}
+    }
+}
+
+thread_local! {
+    static BACKENDS: RefCell<HashMap<(TypeId, Bus), Rc<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// A service followed on a bus, shared by every Tag on the main thread.
+pub(crate) struct Backend<S: Service> {
+    requests: Sender<S::Request>,
+    state: Variable<S::State>,
+}
+
+impl<S: Service> Backend<S> {
+    /// Returns the backend following the service on a bus, starting it if it isn't yet.
+    pub(crate) fn on(bus: &Bus) -> Rc<Self> {
+        let key = (TypeId::of::<S>(), bus.clone());
+
+        let backend = BACKENDS.with(|backends| {
+            Rc::clone(backends.borrow_mut().entry(key).or_insert_with(|| {
+                let (requests, receiver) = channel::<S::Request>();
+                let (sender, states) = channel::<S::State>();
+                let bus = bus.clone();
+
+                thread::spawn(move || serve::<S>(bus, receiver, sender));
+
+                Rc::new(Backend::<S> {
+                    requests,
+                    state: Variable::listen(S::State::default(), states),
+                })
+            }))
+        });
+
+        backend
+            .downcast()
+            .unwrap_or_else(|_| unreachable!("backends are keyed by their service"))
+    }
+
+    /// Returns a `Variable` with the service's state.
+    pub(crate) fn state(&self) -> Variable<S::State> {
+        self.state.clone()
+    }
+
+    pub(crate) fn send(&self, request: S::Request) {
+        let _ = self.requests.send(request);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::sources::fixture::TestBus;
+
+    /// Counts the requests it handles, without any signals to listen to.
+    struct Counter {
+        count: u32,
+        changed: Arc<AtomicBool>,
+    }
+
+    impl Service for Counter {
+        type State = u32;
+        type Request = u32;
+
+        fn connect(_: &Connection, changed: &Arc<AtomicBool>) -> Option<Self> {
+            Some(Counter {
+                count: 0,
+                changed: Arc::clone(changed),
+            })
+        }
+
+        fn rules(&self) -> Vec<MatchRule<'static>> {
+            Vec::new()
+        }
+
+        fn read(&mut self, _: &Connection, _: &u32) -> u32 {
+            self.count
+        }
+
+        fn handle(&mut self, _: &Connection, _: &u32, request: u32) {
+            self.count += request;
+            self.changed.store(true, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn handles_requests_without_waiting_for_a_signal() {
+        let test_bus = TestBus::start();
+        let (requests, receiver) = channel();
+        let (sender, states) = channel();
+        let bus = test_bus.bus();
+
+        thread::spawn(move || serve::<Counter>(bus, receiver, sender));
+        assert_eq!(states.recv_timeout(Duration::from_secs(5)), Ok(0));
+
+        requests.send(2).unwrap();
+        requests.send(3).unwrap();
+
+        // Both requests may be handled at once, or one after the other.
+        let handled = (0..2)
+            .map_while(|_| states.recv_timeout(Duration::from_secs(5)).ok())
+            .find(|&state| state == 5);
+        assert_eq!(handled, Some(5));
+    }
+
+    #[test]
+    fn reports_the_default_state_when_the_bus_is_unreachable() {
+        let (_requests, receiver) = channel::<Infallible>();
+        let (sender, states) = channel::<u8>();
+
+        let bus = Bus::Address("unix:path=/nonexistent/chunks-rs-bus".to_string());
+        serve::<Reader>(bus, receiver, sender);
+
+        assert_eq!(states.try_recv(), Ok(0));
+    }
+
+    struct Reader;
+
+    impl Service for Reader {
+        type State = u8;
+        type Request = Infallible;
+
+        fn connect(_: &Connection, _: &Arc<AtomicBool>) -> Option<Self> {
+            Some(Reader)
+        }
+
+        fn rules(&self) -> Vec<MatchRule<'static>> {
+            Vec::new()
+        }
+
+        fn read(&mut self, _: &Connection, _: &u8) -> u8 {
+            1
+        }
+    }
+}
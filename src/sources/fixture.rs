@@ -0,0 +1,278 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{self, Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    message::MessageType,
+    Message,
+};
+
+use crate::sources::Bus;
+
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+/// A directory of fixture files for tests, standing in for `/`. It is removed once dropped.
+pub(crate) struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    /// Creates an empty fixture directory, named after the test using it.
+    pub(crate) fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!("chunks-rs-{}-{}", name, process::id()));
+
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).expect("fixture directory should be created");
+
+        Self { root }
+    }
+
+    /// Creates a fixture directory holding the given files, by path relative to its root.
+    pub(crate) fn with_files(name: &str, files: &[(&str, &str)]) -> Self {
+        let fixture = Self::new(name);
+
+        for (path, contents) in files {
+            fixture.file(path, contents);
+        }
+
+        fixture
+    }
+
+    /// Writes a file relative to the fixture's root, creating its parent directories.
+    pub(crate) fn file(&self, path: &str, contents: &str) -> &Self {
+        let path = self.root.join(path);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("fixture directories should be created");
+        }
+
+        fs::write(path, contents).expect("fixture file should be written");
+        self
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A private `dbus-daemon` for tests, stopped once dropped.
+pub(crate) struct TestBus {
+    daemon: Child,
+    address: String,
+}
+
+impl TestBus {
+    /// Starts a private bus. Panics if `dbus-daemon` isn't installed, rather than letting the
+    /// tests needing one pass without running.
+    pub(crate) fn start() -> Self {
+        Self::start_with(&[])
+    }
+
+    /// Starts a private bus listening on the given address, e.g. to bring up a bus that a
+    /// source already failed to connect to.
+    pub(crate) fn start_at(address: &str) -> Self {
+        Self::start_with(&[&format!("--address={}", address)])
+    }
+
+    fn start_with(args: &[&str]) -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is required");
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().expect("stdout should be piped"))
+            .read_line(&mut address)
+            .expect("dbus-daemon should print its address");
+
+        Self {
+            daemon,
+            address: address.trim().to_string(),
+        }
+    }
+
+    pub(crate) fn bus(&self) -> Bus {
+        Bus::Address(self.address.clone())
+    }
+
+    /// Waits until a name is owned on the bus, so calls don't race its service starting up.
+    pub(crate) fn wait_for(&self, name: &str) {
+        let conn = self.bus().connect().expect("test bus should be reachable");
+        let dbus = conn.with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            Duration::from_secs(5),
+        );
+
+        for _ in 0..500 {
+            let owned: Result<(bool,), dbus::Error> =
+                dbus.method_call("org.freedesktop.DBus", "NameHasOwner", (name,));
+
+            if owned.is_ok_and(|(owned,)| owned) {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("{} was never owned", name);
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// The properties a `MockService` has, by object path and interface.
+pub(crate) type Properties = Vec<(&'static str, &'static str, PropMap)>;
+
+/// Wraps a value for a `PropMap`.
+pub(crate) fn variant(value: impl RefArg + 'static) -> Variant<Box<dyn RefArg>> {
+    Variant(Box::new(value))
+}
+
+/// Builds a `PropMap` from its entries.
+pub(crate) fn props<const N: usize>(entries: [(&str, Variant<Box<dyn RefArg>>); N]) -> PropMap {
+    entries
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+/// A service on a `TestBus`, answering `Get` and `GetAll` from its properties and any other
+/// method call with a closure. It stops once dropped.
+pub(crate) struct MockService {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockService {
+    /// Starts a service owning `name`, returning once it does. Properties are built on the
+    /// service's own thread, as they can't be sent across threads.
+    pub(crate) fn start<P, H>(
+        test_bus: &TestBus,
+        name: &'static str,
+        properties_fn: P,
+        handle_fn: H,
+    ) -> Self
+    where
+        P: FnOnce() -> Properties + Send + 'static,
+        H: FnMut(&Message) -> Option<Message> + Send + 'static,
+    {
+        let (bus, stop) = (test_bus.bus(), Arc::new(AtomicBool::new(false)));
+        let (ready, started) = channel();
+
+        let stopped = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            let mut handle_fn = handle_fn;
+
+            let conn = bus.connect().expect("mock service should connect");
+            conn.request_name(name, false, false, true)
+                .expect("mock service should own its name");
+
+            let properties = properties_fn();
+            let _ = ready.send(());
+
+            while !stopped.load(Ordering::Acquire) {
+                let Ok(Some(message)) = conn
+                    .channel()
+                    .blocking_pop_message(Duration::from_millis(20))
+                else {
+                    continue;
+                };
+
+                if message.msg_type() != MessageType::MethodCall {
+                    continue;
+                }
+
+                let reply = properties_reply(&properties, &message)
+                    .or_else(|| handle_fn(&message))
+                    .unwrap_or_else(|| {
+                        message.error(
+                            &"org.freedesktop.DBus.Error.UnknownMethod".into(),
+                            c"Unknown method",
+                        )
+                    });
+
+                let _ = conn.channel().send(reply);
+            }
+        });
+
+        started.recv().expect("mock service should start");
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MockService {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn properties_reply(properties: &Properties, message: &Message) -> Option<Message> {
+    if message.interface().as_deref() != Some(PROPERTIES) {
+        return None;
+    }
+
+    let path = message.path()?;
+    let find = |interface: &str| {
+        properties
+            .iter()
+            .find(|(object, name, _)| *object == &*path && *name == interface)
+            .map(|(_, _, properties)| properties)
+    };
+
+    match message.member().as_deref() {
+        Some("Get") => {
+            let (interface, property): (&str, &str) = message.read2().ok()?;
+            let value = find(interface)?.get(property)?;
+
+            Some(
+                message
+                    .method_return()
+                    .append1(Variant(value.0.box_clone())),
+            )
+        }
+        Some("GetAll") => {
+            let interface: &str = message.read1().ok()?;
+            let all: PropMap = find(interface)?
+                .iter()
+                .map(|(name, value)| (name.clone(), Variant(value.0.box_clone())))
+                .collect();
+
+            Some(message.method_return().append1(all))
+        }
+        _ => None,
+    }
+}
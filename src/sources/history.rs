@@ -0,0 +1,589 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use gio::glib::{
+    variant::{StaticVariantType, ToVariant},
+    Variant,
+};
+
+use crate::{
+    sources::{Bus, Notification, NotificationImage, Notifications, Urgency},
+    variable::receive,
+    Variable,
+};
+
+/// The most entries the history keeps, dropping the oldest ones first.
+const LIMIT: usize = 100;
+
+/// An entry as stored on disk: its key, app name, app icon, summary, body, urgency, image,
+/// category, desktop entry and when it arrived.
+type Stored = (
+    u64,
+    String,
+    String,
+    String,
+    String,
+    u8,
+    String,
+    String,
+    String,
+    i64,
+);
+
+/// A notification kept in the history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Identifies the entry, across restarts.
+    pub key: u64,
+    /// The notification, without its actions, which can't be invoked once it is closed.
+    pub notification: Notification,
+    /// When the notification arrived, in seconds since the Unix epoch.
+    pub received: i64,
+}
+
+impl HistoryEntry {
+    fn store(&self) -> Stored {
+        let notification = &self.notification;
+
+        // Pixels would bloat the file, so only images that can be loaded again are kept.
+        let image = match &notification.image {
+            Some(NotificationImage::File(path)) => format!("file:{}", path),
+            Some(NotificationImage::Icon(name)) => format!("icon:{}", name),
+            Some(NotificationImage::Data(_)) | None => String::new(),
+        };
+
+        (
+            self.key,
+            notification.app_name.clone(),
+            notification.app_icon.clone(),
+            notification.summary.clone(),
+            notification.body.clone(),
+            notification.urgency.hint(),
+            image,
+            notification.category.clone().unwrap_or_default(),
+            notification.desktop_entry.clone().unwrap_or_default(),
+            self.received,
+        )
+    }
+
+    fn restore(stored: Stored) -> Self {
+        let (
+            key,
+            app_name,
+            app_icon,
+            summary,
+            body,
+            urgency,
+            image,
+            category,
+            desktop_entry,
+            received,
+        ) = stored;
+
+        let image = if let Some(path) = image.strip_prefix("file:") {
+            Some(NotificationImage::File(path.to_string()))
+        } else {
+            image
+                .strip_prefix("icon:")
+                .map(|name| NotificationImage::Icon(name.to_string()))
+        };
+
+        let optional = |value: String| (!value.is_empty()).then_some(value);
+
+        Self {
+            key,
+            notification: Notification {
+                app_name,
+                app_icon,
+                summary,
+                body,
+                urgency: Urgency::from_hint(urgency),
+                image,
+                category: optional(category),
+                desktop_entry: optional(desktop_entry),
+                ..Default::default()
+            },
+            received,
+        }
+    }
+}
+
+/// Returns `$XDG_STATE_HOME/chunks-rs/notifications`, falling back to `~/.local/state`.
+fn default_path() -> PathBuf {
+    let state = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".local/state")
+        });
+
+    state.join("chunks-rs").join("notifications")
+}
+
+/// Reads the history at `path`. A file that can't be parsed is moved aside to a `.bak` file
+/// rather than lost, as the next save would otherwise overwrite it.
+fn load(path: &Path) -> Vec<HistoryEntry> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    let format = Vec::<Stored>::static_variant_type();
+
+    let stored = Variant::parse(Some(format.as_ref()), &text)
+        .ok()
+        .and_then(|variant| variant.get::<Vec<Stored>>());
+
+    match stored {
+        Some(stored) => stored.into_iter().map(HistoryEntry::restore).collect(),
+        None => {
+            let backup = path.with_extension("bak");
+
+            match fs::rename(path, &backup) {
+                Ok(()) => eprintln!(
+                    "chunks-rs: the notification history is corrupt, moved it to {}",
+                    backup.display()
+                ),
+                Err(error) => eprintln!(
+                    "chunks-rs: the notification history is corrupt, and can't be moved aside: {}",
+                    error
+                ),
+            }
+
+            Vec::new()
+        }
+    }
+}
+
+/// Writes the history to a temporary file first, so a crash never leaves half a history.
+fn save(path: &Path, entries: &[HistoryEntry]) {
+    let stored: Vec<Stored> = entries.iter().map(HistoryEntry::store).collect();
+
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+
+    let temporary = path.with_extension("tmp");
+
+    if fs::write(&temporary, stored.to_variant().print(false).as_str()).is_ok() {
+        let _ = fs::rename(&temporary, path);
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Turns notifications into history entries, remembering which entry each notification
+/// recorded this session went into.
+struct Recorder {
+    next_key: u64,
+    recorded: HashMap<u32, u64>,
+}
+
+impl Recorder {
+    fn new(entries: &[HistoryEntry]) -> Self {
+        Self {
+            next_key: entries.iter().map(|entry| entry.key).max().unwrap_or(0) + 1,
+            recorded: HashMap::new(),
+        }
+    }
+
+    /// Records a notification that arrived, returning whether the history changed. Replaced
+    /// notifications update their entry, and transient ones are left out.
+    fn record(
+        &mut self,
+        entries: &mut Vec<HistoryEntry>,
+        notification: Notification,
+        received: i64,
+    ) -> bool {
+        if notification.transient {
+            return false;
+        }
+
+        let notification = Notification {
+            actions: Vec::new(),
+            timeout: None,
+            ..notification
+        };
+
+        if let Some(key) = self.recorded.get(&notification.id) {
+            // Entries dismissed from the history while still up stay dismissed.
+            return match entries.iter_mut().find(|entry| entry.key == *key) {
+                Some(entry) if entry.notification != notification => {
+                    entry.notification = notification;
+                    true
+                }
+                _ => false,
+            };
+        }
+
+        let key = self.next_key;
+        self.next_key += 1;
+        self.recorded.insert(notification.id, key);
+
+        entries.insert(
+            0,
+            HistoryEntry {
+                key,
+                notification,
+                received,
+            },
+        );
+        entries.truncate(LIMIT);
+        true
+    }
+}
+
+/// Records notifications into the history at `path` as the server receives them, so ones
+/// replaced or closed right away are recorded too.
+fn record(bus: &Bus, path: &Path, entries: &Variable<Vec<HistoryEntry>>) {
+    let recorder = RefCell::new(Recorder::new(&entries.get()));
+    let (sender, arrivals) = channel();
+
+    Notifications::on(bus.clone()).record(sender);
+
+    let (target, path) = (entries.clone(), path.to_path_buf());
+
+    receive(arrivals, move |arrived: Vec<Notification>| {
+        let mut entries = target.get();
+        let mut recorder = recorder.borrow_mut();
+        let mut changed = false;
+
+        for notification in arrived {
+            changed |= recorder.record(&mut entries, notification, now());
+        }
+
+        if changed {
+            save(&path, &entries);
+            target.set(entries);
+        }
+    });
+}
+
+thread_local! {
+    static HISTORIES: RefCell<HashMap<PathBuf, Variable<Vec<HistoryEntry>>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The history of notifications, kept on disk so it survives restarts. It holds the last 100
+/// notifications, newest first.
+///
+/// Recording starts once the history is first used, and runs the notification server if it
+/// isn't running yet. Must be used from the GTK main thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    bus: Bus,
+    path: PathBuf,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    /// Records notifications on the session bus into `$XDG_STATE_HOME/chunks-rs/notifications`.
+    pub fn new() -> Self {
+        History::on(Bus::Session)
+    }
+
+    /// Records notifications on the given bus, e.g. a private bus to test with `notify-send`.
+    pub fn on(bus: Bus) -> Self {
+        Self {
+            bus,
+            path: default_path(),
+        }
+    }
+
+    /// Keeps the history in another file.
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = path.as_ref().to_path_buf();
+        self
+    }
+
+    fn entries(&self) -> Variable<Vec<HistoryEntry>> {
+        let existing = HISTORIES.with(|histories| histories.borrow().get(&self.path).cloned());
+
+        if let Some(entries) = existing {
+            return entries;
+        }
+
+        let entries = Variable::new(load(&self.path));
+
+        HISTORIES.with(|histories| {
+            histories
+                .borrow_mut()
+                .insert(self.path.clone(), entries.clone())
+        });
+
+        // Registered after the history is stored, as recording starts right away.
+        record(&self.bus, &self.path, &entries);
+
+        entries
+    }
+
+    /// Updates the history with `update_fn`, and saves it.
+    fn update(&self, update_fn: impl FnOnce(&mut Vec<HistoryEntry>)) {
+        let entries = self.entries();
+        let mut updated = entries.get();
+
+        update_fn(&mut updated);
+
+        save(&self.path, &updated);
+        entries.set(updated);
+    }
+
+    /// Returns a `Variable` with every entry, newest first.
+    pub fn watch(&self) -> Variable<Vec<HistoryEntry>> {
+        self.entries()
+    }
+
+    /// Removes an entry from the history.
+    pub fn dismiss(&self, key: u64) {
+        self.update(|entries| entries.retain(|entry| entry.key != key));
+    }
+
+    /// Removes every entry from the history.
+    pub fn clear(&self) {
+        self.update(Vec::clear);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use dbus::arg::PropMap;
+    use gio::glib::MainContext;
+
+    use super::*;
+    use crate::{
+        sources::fixture::{Fixture, TestBus},
+        DoNotDisturb,
+    };
+
+    const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+
+    /// Iterates a main context until `done` returns true, or panics after a few seconds.
+    fn iterate_until(context: &MainContext, done: impl Fn() -> bool) {
+        let started = Instant::now();
+
+        while !done() {
+            assert!(started.elapsed() < Duration::from_secs(5), "timed out");
+
+            context.iteration(false);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn notification(id: u32, summary: &str) -> Notification {
+        Notification {
+            id,
+            app_name: "app".to_string(),
+            summary: summary.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn entry(key: u64, summary: &str) -> HistoryEntry {
+        HistoryEntry {
+            key,
+            notification: notification(0, summary),
+            received: 1700000000 + key as i64,
+        }
+    }
+
+    fn summaries(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.notification.summary.as_str())
+            .collect()
+    }
+
+    /// Sends a notification with a timeout in milliseconds, returning its id.
+    fn notify(test_bus: &TestBus, summary: &str, expire_timeout: i32) -> u32 {
+        let conn = test_bus.bus().connect().unwrap();
+        let (id,): (u32,) = conn
+            .with_proxy(
+                NOTIFICATIONS,
+                "/org/freedesktop/Notifications",
+                Duration::from_secs(5),
+            )
+            .method_call(
+                NOTIFICATIONS,
+                "Notify",
+                (
+                    "app",
+                    0u32,
+                    "",
+                    summary,
+                    "",
+                    Vec::<String>::new(),
+                    PropMap::new(),
+                    expire_timeout,
+                ),
+            )
+            .unwrap();
+
+        id
+    }
+
+    #[test]
+    fn saves_and_loads_entries() {
+        let fixture = Fixture::new("history-round-trip");
+        let path = fixture.path().join("notifications");
+
+        let mut full = entry(2, "Full");
+        full.notification = Notification {
+            app_icon: "mail".to_string(),
+            body: "Body with <b>markup</b> and \"quotes\"".to_string(),
+            urgency: Urgency::Critical,
+            image: Some(NotificationImage::File("/tmp/a.png".to_string())),
+            category: Some("email.arrived".to_string()),
+            desktop_entry: Some("thunderbird".to_string()),
+            ..full.notification
+        };
+
+        let mut icon = entry(1, "Icon");
+        icon.notification.image = Some(NotificationImage::Icon("mail-unread".to_string()));
+
+        let entries = vec![full, icon];
+        save(&path, &entries);
+
+        assert_eq!(load(&path), entries);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn leaves_pixels_out_of_saved_entries() {
+        let fixture = Fixture::new("history-pixels");
+        let path = fixture.path().join("notifications");
+
+        let mut pixels = entry(1, "Pixels");
+        pixels.notification.image = Some(NotificationImage::Data(Default::default()));
+        save(&path, &[pixels]);
+
+        assert_eq!(load(&path)[0].notification.image, None);
+    }
+
+    #[test]
+    fn moves_corrupt_histories_aside() {
+        let fixture = Fixture::with_files("history-corrupt", &[("notifications", "[(1, 'a'")]);
+        let path = fixture.path().join("notifications");
+
+        assert_eq!(load(&path), Vec::new());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(path.with_extension("bak")).unwrap(),
+            "[(1, 'a'"
+        );
+
+        assert_eq!(load(&fixture.path().join("missing")), Vec::new());
+    }
+
+    #[test]
+    fn keeps_the_newest_entries_up_to_the_limit() {
+        let mut entries = vec![entry(7, "Old")];
+        let mut recorder = Recorder::new(&entries);
+
+        for id in 1..=LIMIT as u32 {
+            assert!(recorder.record(&mut entries, notification(id, &id.to_string()), 0));
+        }
+
+        assert_eq!(entries.len(), LIMIT);
+        assert_eq!(entries[0].key, 8 + LIMIT as u64 - 1);
+        assert_eq!(entries[0].notification.summary, LIMIT.to_string());
+        assert_eq!(entries[LIMIT - 1].notification.summary, "1");
+    }
+
+    #[test]
+    fn updates_replaced_notifications_and_skips_transient_ones() {
+        let mut entries = Vec::new();
+        let mut recorder = Recorder::new(&entries);
+
+        assert!(recorder.record(&mut entries, notification(1, "First"), 10));
+        assert!(recorder.record(&mut entries, notification(1, "Replaced"), 20));
+        assert!(!recorder.record(&mut entries, notification(1, "Replaced"), 30));
+
+        let transient = Notification {
+            transient: true,
+            ..notification(2, "Transient")
+        };
+        assert!(!recorder.record(&mut entries, transient, 40));
+
+        assert_eq!(summaries(&entries), ["Replaced"]);
+        assert_eq!(entries[0].received, 10);
+
+        // An entry dismissed while its notification is up isn't brought back by replacing it.
+        entries.clear();
+        assert!(!recorder.record(&mut entries, notification(1, "Again"), 50));
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn dismisses_and_clears_entries() {
+        let test_bus = TestBus::start();
+        let fixture = Fixture::new("history-dismiss");
+        let path = fixture.path().join("notifications");
+        save(
+            &path,
+            &[entry(3, "Third"), entry(2, "Second"), entry(1, "First")],
+        );
+
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let history = History::on(test_bus.bus()).path(&path);
+
+                history.dismiss(2);
+                assert_eq!(summaries(&history.watch().get()), ["Third", "First"]);
+                assert_eq!(summaries(&load(&path)), ["Third", "First"]);
+
+                history.clear();
+                assert!(history.watch().get().is_empty());
+                assert!(load(&path).is_empty());
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn records_notifications_closed_right_away_while_do_not_disturb_is_on() {
+        let test_bus = TestBus::start();
+        let fixture = Fixture::new("history-record");
+        let path = fixture.path().join("notifications");
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                DoNotDisturb::set(true);
+
+                let entries = History::on(test_bus.bus()).path(&path).watch();
+                test_bus.wait_for(NOTIFICATIONS);
+
+                // Gone again before the main loop ever sees them up.
+                notify(&test_bus, "Brief", 1);
+                notify(&test_bus, "Briefer", 1);
+                thread::sleep(Duration::from_millis(50));
+
+                iterate_until(&context, || entries.get().len() == 2);
+
+                assert_eq!(summaries(&entries.get()), ["Briefer", "Brief"]);
+                assert_eq!(summaries(&load(&path)), ["Briefer", "Brief"]);
+            })
+            .unwrap();
+    }
+}
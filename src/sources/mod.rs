@@ -0,0 +1,35 @@
+mod audio;
+mod backlight;
+mod battery;
+mod bus;
+#[cfg(test)]
+mod fixture;
+mod history;
+mod mpris;
+mod network;
+mod notifications;
+mod storage;
+mod system;
+mod throughput;
+mod tray;
+
+pub use audio::{AppStream, AudioDevice, Mixer, Sink, Source};
+pub use backlight::{Backlight, Brightness};
+pub use battery::{Battery, BatteryInfo, BatteryState, BatteryStatus, IconRamp};
+pub use bus::Bus;
+pub use history::{History, HistoryEntry};
+pub use mpris::{Media, PlaybackStatus, Player};
+pub use network::{
+    AccessPoint, ConnectionKind, Network, NetworkStatus, Wifi, WifiError, WifiState,
+};
+pub use notifications::{
+    Action, ImageData, Notification, NotificationImage, Notifications, Urgency,
+};
+pub use storage::{Mount, Storage, StorageError, Usage};
+pub use system::{
+    Cpu, CpuUsage, Load, LoadAverage, Memory, MemoryUsage, Temperature, Temperatures,
+};
+pub use throughput::{format_rate, Rates, Throughput};
+pub use tray::{MenuItem, Pixmap, Tray, TrayItem, TrayStatus};
+
+pub(crate) use network::read_status;
@@ -0,0 +1,598 @@
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
+
+use dbus::{
+    arg::{prop_cast, PropMap, RefArg},
+    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection},
+    message::MatchRule,
+    Message, Path,
+};
+
+use crate::{
+    sources::{
+        bus::{Backend, Service},
+        Bus,
+    },
+    Variable,
+};
+
+const DBUS: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT: &str = "org.mpris.MediaPlayer2";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether a player is playing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl PlaybackStatus {
+    fn from_mpris(status: &str) -> Self {
+        match status {
+            "Playing" => PlaybackStatus::Playing,
+            "Paused" => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        }
+    }
+}
+
+/// A media player on the session bus, and the track it is playing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Player {
+    /// The player's bus name, e.g. "org.mpris.MediaPlayer2.spotify".
+    pub bus_name: String,
+    /// The player's display name, e.g. "Spotify".
+    pub identity: String,
+    pub status: PlaybackStatus,
+    pub title: String,
+    /// Every artist of the track, joined with commas.
+    pub artist: String,
+    pub album: String,
+    /// The URL of the album art, usually a `file://` or `https://` URL.
+    pub art_url: Option<String>,
+    /// The position as of when the player last reported it, e.g. after seeking. Players don't
+    /// report it while playing, so use `current_position` for the position right now.
+    pub position: Duration,
+    /// The playback speed, 1.0 being normal speed.
+    pub rate: f64,
+    pub length: Option<Duration>,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_seek: bool,
+    track_id: Option<Path<'static>>,
+    /// When `position` was read.
+    read_at: Option<Instant>,
+}
+
+impl Player {
+    /// Returns the position right now, counting the time played since it was reported.
+    pub fn current_position(&self) -> Duration {
+        self.position_at(Instant::now())
+    }
+
+    fn position_at(&self, now: Instant) -> Duration {
+        let Some(read_at) = self
+            .read_at
+            .filter(|_| self.status == PlaybackStatus::Playing)
+        else {
+            return self.position;
+        };
+
+        let played = now.saturating_duration_since(read_at).as_secs_f64() * self.rate;
+        let position = self.position + Duration::try_from_secs_f64(played).unwrap_or_default();
+
+        self.length.map_or(position, |length| position.min(length))
+    }
+}
+
+/// Builds a player from its `Player` properties. Without an identity, it is named after its bus
+/// name.
+fn parse_player(bus_name: &str, identity: Option<String>, properties: &PropMap) -> Player {
+    let mut player = Player {
+        bus_name: bus_name.to_string(),
+        identity: identity.unwrap_or_else(|| bus_name.trim_start_matches(MPRIS_PREFIX).to_string()),
+        status: PlaybackStatus::from_mpris(
+            prop_cast::<String>(properties, "PlaybackStatus").map_or("", |status| status),
+        ),
+        position: properties
+            .get("Position")
+            .and_then(|position| position.as_i64())
+            .map_or(Duration::ZERO, micros),
+        rate: prop_cast(properties, "Rate").copied().unwrap_or(1.0),
+        can_go_next: prop_cast(properties, "CanGoNext").copied().unwrap_or(false),
+        can_go_previous: prop_cast(properties, "CanGoPrevious")
+            .copied()
+            .unwrap_or(false),
+        can_seek: prop_cast(properties, "CanSeek").copied().unwrap_or(false),
+        ..Default::default()
+    };
+
+    if let Some(metadata) = prop_cast::<PropMap>(properties, "Metadata") {
+        let text = |key| {
+            prop_cast::<String>(metadata, key)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        player.title = text("xesam:title");
+        player.album = text("xesam:album");
+        player.artist = prop_cast::<Vec<String>>(metadata, "xesam:artist")
+            .map(|artists| artists.join(", "))
+            .unwrap_or_default();
+        player.art_url = prop_cast::<String>(metadata, "mpris:artUrl")
+            .filter(|url| !url.is_empty())
+            .cloned();
+        // Some players send the length as a u64, so it is read as any integer.
+        player.length = metadata
+            .get("mpris:length")
+            .and_then(|length| {
+                length
+                    .as_i64()
+                    .or_else(|| length.as_u64().map(|length| length as i64))
+            })
+            .map(micros);
+        player.track_id = prop_cast::<Path<'static>>(metadata, "mpris:trackid").cloned();
+    }
+
+    player
+}
+
+fn read_player(conn: &Connection, bus_name: &str) -> Result<Player, dbus::Error> {
+    let proxy = conn.with_proxy(bus_name, MPRIS_PATH, TIMEOUT);
+    let properties: PropMap = proxy.get_all(PLAYER)?;
+
+    Ok(Player {
+        read_at: Some(Instant::now()),
+        ..parse_player(bus_name, proxy.get(ROOT, "Identity").ok(), &properties)
+    })
+}
+
+fn micros(value: i64) -> Duration {
+    Duration::from_micros(value.max(0) as u64)
+}
+
+fn player_names(conn: &Connection) -> Result<Vec<String>, dbus::Error> {
+    let (names,): (Vec<String>,) =
+        conn.with_proxy(DBUS, DBUS_PATH, TIMEOUT)
+            .method_call(DBUS, "ListNames", ())?;
+
+    let mut names: Vec<String> = names
+        .into_iter()
+        .filter(|name| name.starts_with(MPRIS_PREFIX))
+        .collect();
+    names.sort();
+
+    Ok(names)
+}
+
+/// Every player, along with the one controls are sent to.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct MediaState {
+    players: Vec<Player>,
+    active: Option<String>,
+}
+
+impl MediaState {
+    fn active(&self) -> Option<&Player> {
+        let active = self.active.as_ref()?;
+
+        self.players
+            .iter()
+            .find(|player| &player.bus_name == active)
+    }
+}
+
+enum Action {
+    PlayPause,
+    Play,
+    Pause,
+    Next,
+    Previous,
+    Seek(i64),
+    SetPosition(Duration),
+}
+
+enum Request {
+    /// Sends an action to a player, or the active one if no bus name is given.
+    Player(Option<String>, Action),
+}
+
+fn handle(conn: &Connection, state: &MediaState, request: Request) -> Result<(), dbus::Error> {
+    let Request::Player(bus_name, action) = request;
+
+    let Some(player) = (match bus_name {
+        Some(bus_name) => state
+            .players
+            .iter()
+            .find(|player| player.bus_name == bus_name),
+        None => state.active(),
+    }) else {
+        return Ok(());
+    };
+
+    let proxy = conn.with_proxy(&player.bus_name, MPRIS_PATH, TIMEOUT);
+
+    match action {
+        Action::PlayPause => proxy.method_call(PLAYER, "PlayPause", ()),
+        Action::Play => proxy.method_call(PLAYER, "Play", ()),
+        Action::Pause => proxy.method_call(PLAYER, "Pause", ()),
+        Action::Next => proxy.method_call(PLAYER, "Next", ()),
+        Action::Previous => proxy.method_call(PLAYER, "Previous", ()),
+        Action::Seek(offset) => proxy.method_call(PLAYER, "Seek", (offset,)),
+        Action::SetPosition(position) => match &player.track_id {
+            Some(track_id) => proxy.method_call(
+                PLAYER,
+                "SetPosition",
+                (track_id.clone(), position.as_micros() as i64),
+            ),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Reads every player, and picks the one controls go to: the one that started playing most
+/// recently, or the last one to play if none is playing.
+fn read_state(conn: &Connection, previous: &MediaState, recent: &mut Vec<String>) -> MediaState {
+    let players: Vec<Player> = player_names(conn)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|name| read_player(conn, name).ok())
+        .collect();
+
+    let is_playing = |players: &[Player], name: &str| {
+        players
+            .iter()
+            .any(|player| player.bus_name == name && player.status == PlaybackStatus::Playing)
+    };
+
+    recent.retain(|name| players.iter().any(|player| &player.bus_name == name));
+
+    for player in &players {
+        if player.status == PlaybackStatus::Playing
+            && !is_playing(&previous.players, &player.bus_name)
+        {
+            recent.retain(|name| name != &player.bus_name);
+            recent.insert(0, player.bus_name.clone());
+        }
+    }
+
+    let active = recent
+        .iter()
+        .find(|name| is_playing(&players, name))
+        .or(recent.first())
+        .cloned()
+        .or_else(|| players.first().map(|player| player.bus_name.clone()));
+
+    MediaState { players, active }
+}
+
+/// The players on a bus, read again whenever one appears, disappears or reports a change.
+/// Players only report their position when it jumps, e.g. on `Seeked`, so it isn't polled;
+/// `Player::current_position` counts the time played since instead.
+struct Players {
+    /// Players by bus name, most recently started first.
+    recent: Vec<String>,
+}
+
+impl Service for Players {
+    type State = MediaState;
+    type Request = Request;
+
+    fn connect(_: &Connection, _: &Arc<AtomicBool>) -> Option<Self> {
+        Some(Players { recent: Vec::new() })
+    }
+
+    fn rules(&self) -> Vec<MatchRule<'static>> {
+        vec![
+            MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+                .with_path(MPRIS_PATH),
+            MatchRule::new_signal(PLAYER, "Seeked").with_path(MPRIS_PATH),
+            MatchRule::new_signal(DBUS, "NameOwnerChanged").with_sender(DBUS),
+        ]
+    }
+
+    fn relevant(message: &Message) -> bool {
+        // Only players appearing or disappearing matter, not every client on the bus.
+        message.member().as_deref() != Some("NameOwnerChanged")
+            || message
+                .get1::<&str>()
+                .is_some_and(|name| name.starts_with(MPRIS_PREFIX))
+    }
+
+    fn read(&mut self, conn: &Connection, previous: &MediaState) -> MediaState {
+        read_state(conn, previous, &mut self.recent)
+    }
+
+    fn handle(&mut self, conn: &Connection, state: &MediaState, request: Request) {
+        let _ = handle(conn, state, request);
+    }
+}
+
+/// Follows and controls MPRIS media players, such as Spotify, mpv or browsers.
+///
+/// Controls go to the active player: the one that started playing most recently, or the last
+/// one to play if none is playing. Must be used from the GTK main thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Media {
+    bus: Bus,
+}
+
+impl Default for Media {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Media {
+    /// Follows the players on the session bus.
+    pub fn new() -> Self {
+        Media::on(Bus::Session)
+    }
+
+    /// Follows the players on the given bus, e.g. a mock player on a private bus.
+    pub fn on(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    /// Returns a `Variable` with the active player, or `None` without any player. It updates
+    /// as soon as a player reports a change, but not as the position advances while playing:
+    /// read `Player::current_position` on a timer for that.
+    pub fn watch(&self) -> Variable<Option<Player>> {
+        Backend::<Players>::on(&self.bus)
+            .state()
+            .map(|state| state.active().cloned())
+    }
+
+    /// Returns a `Variable` with every player, sorted by bus name.
+    pub fn players(&self) -> Variable<Vec<Player>> {
+        Backend::<Players>::on(&self.bus)
+            .state()
+            .map(|state| state.players.clone())
+    }
+
+    pub fn play_pause(&self) {
+        self.send(None, Action::PlayPause);
+    }
+
+    pub fn play(&self) {
+        self.send(None, Action::Play);
+    }
+
+    pub fn pause(&self) {
+        self.send(None, Action::Pause);
+    }
+
+    pub fn next(&self) {
+        self.send(None, Action::Next);
+    }
+
+    pub fn previous(&self) {
+        self.send(None, Action::Previous);
+    }
+
+    /// Seeks forward, or backward for a negative offset, by a number of seconds.
+    pub fn seek(&self, offset: i64) {
+        self.send(None, Action::Seek(offset * 1_000_000));
+    }
+
+    /// Jumps to a position in the current track.
+    pub fn set_position(&self, position: Duration) {
+        self.send(None, Action::SetPosition(position));
+    }
+
+    /// Toggles playback of a specific player, by bus name.
+    pub fn play_pause_player(&self, bus_name: &str) {
+        self.send(Some(bus_name), Action::PlayPause);
+    }
+
+    fn send(&self, bus_name: Option<&str>, action: Action) {
+        Backend::<Players>::on(&self.bus)
+            .send(Request::Player(bus_name.map(str::to_string), action));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use dbus::arg::Variant;
+
+    use super::*;
+    use crate::sources::fixture::{props, variant, MockService, Properties, TestBus};
+
+    const TEST: &str = "org.mpris.MediaPlayer2.test";
+    const OTHER: &str = "org.mpris.MediaPlayer2.other";
+    const TRACK: &str = "/org/mpris/MediaPlayer2/track/1";
+
+    fn metadata(length: Box<dyn RefArg>, art_url: &str) -> PropMap {
+        props([
+            ("mpris:trackid", variant(Path::from(TRACK))),
+            ("mpris:length", Variant(length)),
+            ("mpris:artUrl", variant(art_url.to_string())),
+            ("xesam:title", variant("Song".to_string())),
+            ("xesam:album", variant("Album".to_string())),
+            (
+                "xesam:artist",
+                variant(vec!["First".to_string(), "Second".to_string()]),
+            ),
+        ])
+    }
+
+    fn player_properties(status: &str, metadata: PropMap) -> PropMap {
+        props([
+            ("PlaybackStatus", variant(status.to_string())),
+            ("Position", variant(30_000_000i64)),
+            ("CanGoNext", variant(true)),
+            ("CanGoPrevious", variant(false)),
+            ("CanSeek", variant(true)),
+            ("Metadata", variant(metadata)),
+        ])
+    }
+
+    fn mock_player(status: &'static str, identity: &'static str) -> impl FnOnce() -> Properties {
+        move || {
+            let metadata = metadata(Box::new(180_000_000i64), "file:///tmp/cover.png");
+
+            vec![
+                (
+                    MPRIS_PATH,
+                    ROOT,
+                    props([("Identity", variant(identity.to_string()))]),
+                ),
+                (MPRIS_PATH, PLAYER, player_properties(status, metadata)),
+            ]
+        }
+    }
+
+    #[test]
+    fn parses_player_metadata() {
+        let properties = player_properties(
+            "Playing",
+            metadata(Box::new(180_000_000i64), "file:///tmp/cover.png"),
+        );
+
+        assert_eq!(
+            parse_player(TEST, Some("Test".to_string()), &properties),
+            Player {
+                bus_name: TEST.to_string(),
+                identity: "Test".to_string(),
+                status: PlaybackStatus::Playing,
+                title: "Song".to_string(),
+                artist: "First, Second".to_string(),
+                album: "Album".to_string(),
+                art_url: Some("file:///tmp/cover.png".to_string()),
+                position: Duration::from_secs(30),
+                rate: 1.0,
+                length: Some(Duration::from_secs(180)),
+                can_go_next: true,
+                can_go_previous: false,
+                can_seek: true,
+                track_id: Some(Path::from(TRACK)),
+                read_at: None,
+            }
+        );
+    }
+
+    #[test]
+    fn extrapolates_the_position_while_playing() {
+        let read_at = Instant::now();
+        let player = Player {
+            status: PlaybackStatus::Playing,
+            position: Duration::from_secs(30),
+            rate: 1.5,
+            length: Some(Duration::from_secs(180)),
+            read_at: Some(read_at),
+            ..Default::default()
+        };
+
+        assert_eq!(player.position_at(read_at), Duration::from_secs(30));
+        assert_eq!(
+            player.position_at(read_at + Duration::from_secs(10)),
+            Duration::from_secs(45)
+        );
+        assert_eq!(
+            player.position_at(read_at + Duration::from_secs(600)),
+            Duration::from_secs(180)
+        );
+
+        let paused = Player {
+            status: PlaybackStatus::Paused,
+            ..player.clone()
+        };
+        assert_eq!(
+            paused.position_at(read_at + Duration::from_secs(10)),
+            Duration::from_secs(30)
+        );
+
+        let rewinding = Player {
+            rate: -1.0,
+            ..player
+        };
+        assert_eq!(
+            rewinding.position_at(read_at + Duration::from_secs(10)),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn parses_unsigned_lengths_and_missing_art() {
+        let properties = player_properties("Paused", metadata(Box::new(60_000_000u64), ""));
+        let player = parse_player(TEST, None, &properties);
+
+        assert_eq!(player.identity, "test");
+        assert_eq!(player.status, PlaybackStatus::Paused);
+        assert_eq!(player.length, Some(Duration::from_secs(60)));
+        assert_eq!(player.art_url, None);
+    }
+
+    #[test]
+    fn parses_players_without_metadata() {
+        let properties = props([("PlaybackStatus", variant("Unknown".to_string()))]);
+
+        assert_eq!(
+            parse_player(TEST, None, &properties),
+            Player {
+                bus_name: TEST.to_string(),
+                identity: "test".to_string(),
+                rate: 1.0,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reads_players_and_picks_the_playing_one() {
+        let test_bus = TestBus::start();
+        let _other = MockService::start(&test_bus, OTHER, mock_player("Paused", "Other"), |_| None);
+        let _test = MockService::start(&test_bus, TEST, mock_player("Playing", "Test"), |_| None);
+        let conn = test_bus.bus().connect().unwrap();
+
+        let mut players = Players { recent: Vec::new() };
+        let state = players.read(&conn, &MediaState::default());
+
+        assert_eq!(
+            state
+                .players
+                .iter()
+                .map(|player| (player.identity.as_str(), player.status))
+                .collect::<Vec<_>>(),
+            [
+                ("Other", PlaybackStatus::Paused),
+                ("Test", PlaybackStatus::Playing)
+            ]
+        );
+        assert_eq!(state.active.as_deref(), Some(TEST));
+        assert_eq!(players.recent, [TEST]);
+    }
+
+    #[test]
+    fn sends_actions_to_the_active_player() {
+        let test_bus = TestBus::start();
+        let (sender, receiver) = channel();
+        let _test = MockService::start(
+            &test_bus,
+            TEST,
+            mock_player("Playing", "Test"),
+            move |message| {
+                let _ = sender.send(message.member()?.to_string());
+                Some(message.method_return())
+            },
+        );
+        let conn = test_bus.bus().connect().unwrap();
+
+        let mut players = Players { recent: Vec::new() };
+        let state = players.read(&conn, &MediaState::default());
+        players.handle(&conn, &state, Request::Player(None, Action::PlayPause));
+
+        assert_eq!(receiver.recv_timeout(TIMEOUT), Ok("PlayPause".to_string()));
+    }
+}
@@ -0,0 +1,1015 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dbus::{
+    arg::{prop_cast, Get, PropMap, Variant},
+    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection},
+    message::MatchRule,
+    Path,
+};
+
+use crate::{
+    sources::{
+        bus::{Backend, Service},
+        Bus,
+    },
+    Variable,
+};
+
+const NM: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const DEVICE: &str = "org.freedesktop.NetworkManager.Device";
+const WIRELESS: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const ACCESS_POINT: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const IP4_CONFIG: &str = "org.freedesktop.NetworkManager.IP4Config";
+const IP6_CONFIG: &str = "org.freedesktop.NetworkManager.IP6Config";
+const SETTINGS: &str = "org.freedesktop.NetworkManager.Settings";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+const SETTINGS_CONNECTION: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The type of the primary network connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionKind {
+    #[default]
+    Disconnected,
+    Wired,
+    Wireless,
+    Vpn,
+    Other,
+}
+
+impl ConnectionKind {
+    fn from_type(kind: &str) -> Self {
+        match kind {
+            "802-3-ethernet" => ConnectionKind::Wired,
+            "802-11-wireless" => ConnectionKind::Wireless,
+            "vpn" | "wireguard" => ConnectionKind::Vpn,
+            _ => ConnectionKind::Other,
+        }
+    }
+}
+
+/// The state of the network, as reported by NetworkManager.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkStatus {
+    /// Type of the primary connection, i.e. the one holding the default route.
+    pub kind: ConnectionKind,
+    /// Name of the primary connection's profile.
+    pub name: Option<String>,
+    /// SSID of the access point the primary connection is connected to, if it is wireless.
+    pub ssid: Option<String>,
+    /// Signal strength of that access point, in percent.
+    pub strength: Option<u8>,
+    /// IPv4 addresses of the primary connection, e.g. `192.168.1.20/24`.
+    pub ipv4: Vec<String>,
+    /// IPv6 addresses of the primary connection.
+    pub ipv6: Vec<String>,
+    /// Whether any VPN connection is active.
+    pub vpn: bool,
+}
+
+fn get<T>(conn: &Connection, path: &Path, interface: &str, property: &str) -> Result<T, dbus::Error>
+where
+    T: for<'b> Get<'b> + 'static,
+{
+    conn.with_proxy(NM, path, TIMEOUT).get(interface, property)
+}
+
+fn addresses(conn: &Connection, config: &Path, interface: &str) -> Vec<String> {
+    if &**config == "/" {
+        return Vec::new();
+    }
+
+    get::<Vec<PropMap>>(conn, config, interface, "AddressData")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|data| {
+            let address = prop_cast::<String>(data, "address")?;
+            let prefix = prop_cast::<u32>(data, "prefix")?;
+
+            Some(format!("{}/{}", address, prefix))
+        })
+        .collect()
+}
+
+pub(crate) fn read_status(conn: &Connection) -> Result<NetworkStatus, dbus::Error> {
+    let nm_path = Path::from(NM_PATH);
+    let primary: Path<'static> = get(conn, &nm_path, NM, "PrimaryConnection")?;
+    let active: Vec<Path<'static>> = get(conn, &nm_path, NM, "ActiveConnections")?;
+
+    let mut status = NetworkStatus {
+        vpn: active.iter().any(|path| {
+            get::<bool>(conn, path, ACTIVE, "Vpn").unwrap_or(false)
+                || get::<String>(conn, path, ACTIVE, "Type").is_ok_and(|kind| kind == "wireguard")
+        }),
+        ..Default::default()
+    };
+
+    if &*primary == "/" {
+        return Ok(status);
+    }
+
+    status.kind = ConnectionKind::from_type(&get::<String>(conn, &primary, ACTIVE, "Type")?);
+    status.name = get(conn, &primary, ACTIVE, "Id").ok();
+
+    if let Ok(config) = get::<Path<'static>>(conn, &primary, ACTIVE, "Ip4Config") {
+        status.ipv4 = addresses(conn, &config, IP4_CONFIG);
+    }
+
+    if let Ok(config) = get::<Path<'static>>(conn, &primary, ACTIVE, "Ip6Config") {
+        status.ipv6 = addresses(conn, &config, IP6_CONFIG);
+    }
+
+    if status.kind == ConnectionKind::Wireless {
+        let devices: Vec<Path<'static>> = get(conn, &primary, ACTIVE, "Devices")?;
+
+        for device in devices {
+            let Ok(ap) = get::<Path<'static>>(conn, &device, WIRELESS, "ActiveAccessPoint") else {
+                continue;
+            };
+
+            if &*ap != "/" {
+                let ssid: Vec<u8> = get(conn, &ap, ACCESS_POINT, "Ssid")?;
+
+                status.ssid = Some(String::from_utf8_lossy(&ssid).to_string());
+                status.strength = get(conn, &ap, ACCESS_POINT, "Strength").ok();
+                break;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+/// A Wi-Fi network, merged from every access point broadcasting the same SSID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessPoint {
+    pub ssid: String,
+    /// Signal strength of the strongest access point, in percent.
+    pub strength: u8,
+    /// Whether the network requires a passphrase.
+    pub secured: bool,
+    /// Whether a connection profile for the network is saved.
+    pub known: bool,
+    /// Whether the network is currently connected.
+    pub active: bool,
+}
+
+/// Why a Wi-Fi action failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WifiError {
+    /// NetworkManager has no Wi-Fi device.
+    NoDevice,
+    /// No visible network has the given SSID.
+    NotFound(String),
+    /// The network is secured, but no passphrase was given.
+    PassphraseRequired(String),
+    /// The network can't be joined with a passphrase alone, e.g. WPA-Enterprise or WEP.
+    Unsupported(String),
+    /// NetworkManager refused the request.
+    Failed(String),
+}
+
+impl Display for WifiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiError::NoDevice => write!(f, "no Wi-Fi device is available"),
+            WifiError::NotFound(ssid) => write!(f, "no network named {} is visible", ssid),
+            WifiError::PassphraseRequired(ssid) => write!(f, "{} needs a passphrase", ssid),
+            WifiError::Unsupported(ssid) => {
+                write!(f, "{} can't be joined with a passphrase alone", ssid)
+            }
+            WifiError::Failed(message) => write!(f, "NetworkManager failed: {}", message),
+        }
+    }
+}
+
+impl Error for WifiError {}
+
+impl From<dbus::Error> for WifiError {
+    fn from(error: dbus::Error) -> Self {
+        WifiError::Failed(error.message().unwrap_or_default().to_string())
+    }
+}
+
+/// The state of the Wi-Fi radio and the networks it can see.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WifiState {
+    /// Whether the Wi-Fi radio is turned on.
+    pub enabled: bool,
+    /// Visible networks, strongest first. Hidden networks are left out.
+    pub access_points: Vec<AccessPoint>,
+    /// Why the last action failed, if it did. Cleared once an action succeeds.
+    pub error: Option<WifiError>,
+}
+
+/// How a network is secured, from the flags of its access point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Security {
+    /// No passphrase is needed. This includes Enhanced Open (OWE), which encrypts without one.
+    Open,
+    /// WPA or WPA2 Personal, including WPA2/WPA3 transition networks.
+    Psk,
+    /// WPA3 Personal only.
+    Sae,
+    /// WPA-Enterprise, or WEP.
+    Unsupported,
+}
+
+impl Security {
+    /// Reads NM80211ApFlags and NM80211ApSecurityFlags.
+    fn from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> Self {
+        const PRIVACY: u32 = 0x1;
+        const PSK: u32 = 0x100;
+        const EAP: u32 = 0x200;
+        const SAE: u32 = 0x400;
+        const OWE: u32 = 0x800 | 0x1000;
+        const EAP_SUITE_B: u32 = 0x2000;
+
+        let security = wpa_flags | rsn_flags;
+
+        if security & PSK != 0 {
+            Security::Psk
+        } else if security & SAE != 0 {
+            Security::Sae
+        } else if security & (EAP | EAP_SUITE_B) != 0 {
+            Security::Unsupported
+        } else if security & OWE != 0 {
+            Security::Open
+        } else if flags & PRIVACY != 0 {
+            Security::Unsupported
+        } else {
+            Security::Open
+        }
+    }
+
+    /// The `key-mgmt` NetworkManager needs to join with a passphrase.
+    fn key_mgmt(self) -> Option<&'static str> {
+        match self {
+            Security::Psk => Some("wpa-psk"),
+            Security::Sae => Some("sae"),
+            Security::Open | Security::Unsupported => None,
+        }
+    }
+}
+
+/// Returns the first Wi-Fi device NetworkManager lists. Any other Wi-Fi device is ignored.
+fn wifi_device(conn: &Connection) -> Option<Path<'static>> {
+    let devices: Vec<Path<'static>> = get(conn, &Path::from(NM_PATH), NM, "Devices").ok()?;
+
+    devices
+        .into_iter()
+        .find(|device| get::<u32>(conn, device, DEVICE, "DeviceType").is_ok_and(|kind| kind == 2))
+}
+
+/// Returns every saved Wi-Fi connection profile, by SSID.
+fn known_networks(conn: &Connection) -> HashMap<String, Path<'static>> {
+    let proxy = conn.with_proxy(NM, SETTINGS_PATH, TIMEOUT);
+    let Ok((profiles,)) =
+        proxy.method_call::<(Vec<Path<'static>>,), _, _, _>(SETTINGS, "ListConnections", ())
+    else {
+        return HashMap::new();
+    };
+
+    profiles
+        .into_iter()
+        .filter_map(|profile| {
+            let (settings,): (HashMap<String, PropMap>,) = conn
+                .with_proxy(NM, &profile, TIMEOUT)
+                .method_call(SETTINGS_CONNECTION, "GetSettings", ())
+                .ok()?;
+            let ssid = prop_cast::<Vec<u8>>(settings.get("802-11-wireless")?, "ssid")?;
+
+            Some((String::from_utf8_lossy(ssid).to_string(), profile))
+        })
+        .collect()
+}
+
+/// Returns every visible access point with its SSID and security, by path.
+fn scanned_access_points(
+    conn: &Connection,
+    device: &Path,
+) -> Vec<(Path<'static>, AccessPoint, Security)> {
+    let paths: Vec<Path<'static>> = get(conn, device, WIRELESS, "AccessPoints").unwrap_or_default();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let ssid: Vec<u8> = get(conn, &path, ACCESS_POINT, "Ssid").ok()?;
+            let flags: u32 = get(conn, &path, ACCESS_POINT, "Flags").unwrap_or(0);
+            let wpa_flags: u32 = get(conn, &path, ACCESS_POINT, "WpaFlags").unwrap_or(0);
+            let rsn_flags: u32 = get(conn, &path, ACCESS_POINT, "RsnFlags").unwrap_or(0);
+
+            let security = Security::from_flags(flags, wpa_flags, rsn_flags);
+
+            let access_point = AccessPoint {
+                ssid: String::from_utf8_lossy(&ssid).to_string(),
+                strength: get(conn, &path, ACCESS_POINT, "Strength").unwrap_or(0),
+                secured: security != Security::Open,
+                ..Default::default()
+            };
+
+            Some((path, access_point, security))
+        })
+        .collect()
+}
+
+fn read_wifi(conn: &Connection) -> WifiState {
+    let mut state = WifiState {
+        enabled: get(conn, &Path::from(NM_PATH), NM, "WirelessEnabled").unwrap_or(false),
+        ..Default::default()
+    };
+
+    let Some(device) = wifi_device(conn) else {
+        return state;
+    };
+
+    let active: Option<Path<'static>> = get(conn, &device, WIRELESS, "ActiveAccessPoint").ok();
+    let known = known_networks(conn);
+
+    for (path, mut access_point, _) in scanned_access_points(conn, &device) {
+        if access_point.ssid.is_empty() {
+            continue;
+        }
+
+        access_point.known = known.contains_key(&access_point.ssid);
+        access_point.active = active.as_ref() == Some(&path);
+
+        match state
+            .access_points
+            .iter_mut()
+            .find(|seen| seen.ssid == access_point.ssid)
+        {
+            Some(seen) => {
+                seen.strength = seen.strength.max(access_point.strength);
+                seen.active |= access_point.active;
+            }
+            None => state.access_points.push(access_point),
+        }
+    }
+
+    state
+        .access_points
+        .sort_by_key(|access_point| Reverse(access_point.strength));
+
+    state
+}
+
+/// Everything the NetworkManager thread reports back to the main thread.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NetworkState {
+    status: NetworkStatus,
+    wifi: WifiState,
+}
+
+enum Request {
+    Rescan,
+    Connect(String, Option<String>),
+    Disconnect,
+    SetEnabled(bool),
+}
+
+fn handle(conn: &Connection, request: Request) -> Result<(), WifiError> {
+    let nm = conn.with_proxy(NM, NM_PATH, TIMEOUT);
+
+    if let Request::SetEnabled(enabled) = request {
+        return Ok(nm.set(NM, "WirelessEnabled", enabled)?);
+    }
+
+    let device = wifi_device(conn).ok_or(WifiError::NoDevice)?;
+    let wifi = conn.with_proxy(NM, &device, TIMEOUT);
+
+    match request {
+        Request::SetEnabled(_) => Ok(()),
+        Request::Rescan => Ok(wifi.method_call(WIRELESS, "RequestScan", (PropMap::new(),))?),
+        Request::Disconnect => Ok(wifi.method_call(DEVICE, "Disconnect", ())?),
+        Request::Connect(ssid, passphrase) => {
+            if let Some(profile) = known_networks(conn).remove(&ssid) {
+                let root = Path::from("/");
+                let _: (Path<'static>,) =
+                    nm.method_call(NM, "ActivateConnection", (profile, &device, root))?;
+
+                return Ok(());
+            }
+
+            let Some((access_point, _, security)) = scanned_access_points(conn, &device)
+                .into_iter()
+                .find(|(_, access_point, _)| access_point.ssid == ssid)
+            else {
+                return Err(WifiError::NotFound(ssid));
+            };
+
+            // NetworkManager fills in the rest of the profile from the access point.
+            let mut settings: HashMap<String, PropMap> = HashMap::new();
+
+            match (security, security.key_mgmt(), passphrase) {
+                (Security::Open, ..) => (),
+                (_, None, _) => return Err(WifiError::Unsupported(ssid)),
+                (_, Some(_), None) => return Err(WifiError::PassphraseRequired(ssid)),
+                (_, Some(key_mgmt), Some(passphrase)) => {
+                    let mut security = PropMap::new();
+                    security.insert("key-mgmt".into(), Variant(Box::new(key_mgmt.to_string())));
+                    security.insert("psk".into(), Variant(Box::new(passphrase)));
+
+                    settings.insert("802-11-wireless-security".into(), security);
+                }
+            }
+
+            let _: (Path<'static>, Path<'static>) = nm.method_call(
+                NM,
+                "AddAndActivateConnection",
+                (settings, &device, access_point),
+            )?;
+
+            Ok(())
+        }
+    }
+}
+
+/// NetworkManager, read again whenever it emits a signal.
+struct NetworkManager {
+    changed: Arc<AtomicBool>,
+    /// Why the last request failed, if it did.
+    error: Option<WifiError>,
+}
+
+impl Service for NetworkManager {
+    type State = NetworkState;
+    type Request = Request;
+
+    fn connect(_: &Connection, changed: &Arc<AtomicBool>) -> Option<Self> {
+        Some(NetworkManager {
+            changed: Arc::clone(changed),
+            error: None,
+        })
+    }
+
+    fn rules(&self) -> Vec<MatchRule<'static>> {
+        // Only the objects the state is read from, rather than every DHCP lease or checkpoint.
+        let changed = || MatchRule::new_signal(PROPERTIES, "PropertiesChanged").with_sender(NM);
+        let mut rules = vec![
+            changed().with_path(NM_PATH),
+            MatchRule::new_signal(WIRELESS, "AccessPointAdded").with_sender(NM),
+            MatchRule::new_signal(WIRELESS, "AccessPointRemoved").with_sender(NM),
+            MatchRule::new_signal(SETTINGS, "NewConnection").with_sender(NM),
+            MatchRule::new_signal(SETTINGS, "ConnectionRemoved").with_sender(NM),
+        ];
+
+        rules.extend(
+            [
+                "ActiveConnection",
+                "Devices",
+                "AccessPoint",
+                "IP4Config",
+                "IP6Config",
+            ]
+            .map(|kind| changed().with_namespaced_path(format!("{}/{}", NM_PATH, kind))),
+        );
+
+        rules
+    }
+
+    fn read(&mut self, conn: &Connection, _: &NetworkState) -> NetworkState {
+        NetworkState {
+            status: read_status(conn).unwrap_or_default(),
+            wifi: WifiState {
+                error: self.error.clone(),
+                ..read_wifi(conn)
+            },
+        }
+    }
+
+    fn handle(&mut self, conn: &Connection, _: &NetworkState, request: Request) {
+        let error = handle(conn, request).err();
+
+        // Read again to report the error, or to clear the previous one.
+        if error != self.error {
+            self.error = error;
+            self.changed.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// The network connection, as managed by NetworkManager.
+pub struct Network;
+
+impl Network {
+    /// Returns a `Variable` following NetworkManager on the system bus. It updates as soon as
+    /// NetworkManager reports a change, rather than on an interval.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch() -> Variable<NetworkStatus> {
+        Network::watch_on(Bus::System)
+    }
+
+    /// Same as `watch`, but for a NetworkManager on the given bus.
+    pub fn watch_on(bus: Bus) -> Variable<NetworkStatus> {
+        Backend::<NetworkManager>::on(&bus)
+            .state()
+            .map(|state| state.status.clone())
+    }
+}
+
+/// Controls the Wi-Fi radio through NetworkManager.
+///
+/// Must be used from the GTK main thread. Actions are carried out in the background, and their
+/// results show up in `watch` once NetworkManager reports them. If an action fails, the
+/// reason shows up in `WifiState::error` instead.
+///
+/// Only the first Wi-Fi device NetworkManager lists is followed and controlled, so on machines
+/// with several Wi-Fi adapters, the others are left alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wifi {
+    bus: Bus,
+}
+
+impl Default for Wifi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wifi {
+    /// Controls the Wi-Fi of NetworkManager on the system bus.
+    pub fn new() -> Self {
+        Wifi::on(Bus::System)
+    }
+
+    /// Controls the Wi-Fi of NetworkManager on the given bus.
+    pub fn on(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    /// Returns a `Variable` following the radio state and the visible networks.
+    pub fn watch(&self) -> Variable<WifiState> {
+        Backend::<NetworkManager>::on(&self.bus)
+            .state()
+            .map(|state| state.wifi.clone())
+    }
+
+    /// Asks the Wi-Fi device to scan for networks.
+    pub fn rescan(&self) {
+        Backend::<NetworkManager>::on(&self.bus).send(Request::Rescan);
+    }
+
+    /// Connects to a network. Known networks reuse their saved profile; for new networks a
+    /// profile is created, using the passphrase if the network is secured. WPA/WPA2 and WPA3
+    /// Personal networks are supported; joining a WPA-Enterprise or WEP network fails with
+    /// `WifiError::Unsupported`.
+    pub fn connect(&self, ssid: &str, passphrase: Option<&str>) {
+        Backend::<NetworkManager>::on(&self.bus).send(Request::Connect(
+            ssid.to_string(),
+            passphrase.map(str::to_string),
+        ));
+    }
+
+    /// Disconnects the Wi-Fi device from its network.
+    pub fn disconnect(&self) {
+        Backend::<NetworkManager>::on(&self.bus).send(Request::Disconnect);
+    }
+
+    /// Turns the Wi-Fi radio on or off.
+    pub fn set_enabled(&self, enabled: bool) {
+        Backend::<NetworkManager>::on(&self.bus).send(Request::SetEnabled(enabled));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Receiver};
+
+    use dbus::Message;
+
+    use super::*;
+    use crate::sources::fixture::{props, variant, MockService, Properties, TestBus};
+
+    const PRIMARY: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const WIREGUARD: &str = "/org/freedesktop/NetworkManager/ActiveConnection/2";
+    const IP4: &str = "/org/freedesktop/NetworkManager/IP4Config/1";
+    const ETHERNET: &str = "/org/freedesktop/NetworkManager/Devices/1";
+    const WLAN: &str = "/org/freedesktop/NetworkManager/Devices/2";
+    const HOME: &str = "/org/freedesktop/NetworkManager/AccessPoint/1";
+    const HOME_REPEATER: &str = "/org/freedesktop/NetworkManager/AccessPoint/2";
+    const CAFE: &str = "/org/freedesktop/NetworkManager/AccessPoint/3";
+    const HIDDEN: &str = "/org/freedesktop/NetworkManager/AccessPoint/4";
+    const OFFICE: &str = "/org/freedesktop/NetworkManager/AccessPoint/5";
+    const CAMPUS: &str = "/org/freedesktop/NetworkManager/AccessPoint/6";
+    const GUEST: &str = "/org/freedesktop/NetworkManager/AccessPoint/7";
+    const PROFILE: &str = "/org/freedesktop/NetworkManager/Settings/1";
+
+    fn paths(paths: &[&'static str]) -> Vec<Path<'static>> {
+        paths.iter().map(|path| Path::from(*path)).collect()
+    }
+
+    /// An access point with the given NM80211ApSecurityFlags, or an open one for 0.
+    fn access_point(ssid: &str, strength: u8, rsn_flags: u32) -> PropMap {
+        props([
+            ("Ssid", variant(ssid.as_bytes().to_vec())),
+            ("Strength", variant(strength)),
+            ("Flags", variant(u32::from(rsn_flags != 0))),
+            ("WpaFlags", variant(0u32)),
+            ("RsnFlags", variant(rsn_flags)),
+        ])
+    }
+
+    fn network_manager() -> NetworkManager {
+        NetworkManager {
+            changed: Arc::new(AtomicBool::new(false)),
+            error: None,
+        }
+    }
+
+    fn properties() -> Properties {
+        let address = props([
+            ("address", variant("192.168.1.20".to_string())),
+            ("prefix", variant(24u32)),
+        ]);
+
+        vec![
+            (
+                NM_PATH,
+                NM,
+                props([
+                    ("PrimaryConnection", variant(Path::from(PRIMARY))),
+                    ("ActiveConnections", variant(paths(&[PRIMARY, WIREGUARD]))),
+                    ("Devices", variant(paths(&[ETHERNET, WLAN]))),
+                    ("WirelessEnabled", variant(true)),
+                ]),
+            ),
+            (
+                PRIMARY,
+                ACTIVE,
+                props([
+                    ("Type", variant("802-11-wireless".to_string())),
+                    ("Id", variant("Home".to_string())),
+                    ("Vpn", variant(false)),
+                    ("Ip4Config", variant(Path::from(IP4))),
+                    ("Ip6Config", variant(Path::from("/"))),
+                    ("Devices", variant(paths(&[WLAN]))),
+                ]),
+            ),
+            (
+                WIREGUARD,
+                ACTIVE,
+                props([
+                    ("Type", variant("wireguard".to_string())),
+                    ("Vpn", variant(false)),
+                ]),
+            ),
+            (
+                IP4,
+                IP4_CONFIG,
+                props([("AddressData", variant(vec![address]))]),
+            ),
+            (ETHERNET, DEVICE, props([("DeviceType", variant(1u32))])),
+            (WLAN, DEVICE, props([("DeviceType", variant(2u32))])),
+            (
+                WLAN,
+                WIRELESS,
+                props([
+                    ("ActiveAccessPoint", variant(Path::from(HOME))),
+                    (
+                        "AccessPoints",
+                        variant(paths(&[
+                            CAFE,
+                            HOME,
+                            HOME_REPEATER,
+                            HIDDEN,
+                            OFFICE,
+                            CAMPUS,
+                            GUEST,
+                        ])),
+                    ),
+                ]),
+            ),
+            (HOME, ACCESS_POINT, access_point("Home", 60, 0x188)),
+            (HOME_REPEATER, ACCESS_POINT, access_point("Home", 80, 0x188)),
+            (CAFE, ACCESS_POINT, access_point("Cafe", 40, 0)),
+            (HIDDEN, ACCESS_POINT, access_point("", 90, 0)),
+            (OFFICE, ACCESS_POINT, access_point("Office", 30, 0x488)),
+            (CAMPUS, ACCESS_POINT, access_point("Campus", 20, 0x288)),
+            (GUEST, ACCESS_POINT, access_point("Guest", 10, 0x808)),
+        ]
+    }
+
+    /// Answers the settings calls NetworkManager isn't asked through properties for.
+    fn settings(message: &Message) -> Option<Message> {
+        match message.member().as_deref()? {
+            "ListConnections" => Some(message.method_return().append1(paths(&[PROFILE]))),
+            "GetSettings" => {
+                let wireless = props([("ssid", variant(b"Home".to_vec()))]);
+                let settings = HashMap::from([("802-11-wireless".to_string(), wireless)]);
+
+                Some(message.method_return().append1(settings))
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn maps_connection_types_to_kinds() {
+        assert_eq!(
+            ConnectionKind::from_type("802-3-ethernet"),
+            ConnectionKind::Wired
+        );
+        assert_eq!(
+            ConnectionKind::from_type("802-11-wireless"),
+            ConnectionKind::Wireless
+        );
+        assert_eq!(ConnectionKind::from_type("vpn"), ConnectionKind::Vpn);
+        assert_eq!(ConnectionKind::from_type("wireguard"), ConnectionKind::Vpn);
+        assert_eq!(ConnectionKind::from_type("bridge"), ConnectionKind::Other);
+    }
+
+    #[test]
+    fn reads_the_primary_connection_and_visible_networks() {
+        let test_bus = TestBus::start();
+        let _nm = MockService::start(&test_bus, NM, properties, settings);
+        let conn = test_bus.bus().connect().unwrap();
+
+        let state = network_manager().read(&conn, &NetworkState::default());
+
+        assert_eq!(
+            state.status,
+            NetworkStatus {
+                kind: ConnectionKind::Wireless,
+                name: Some("Home".into()),
+                ssid: Some("Home".into()),
+                strength: Some(60),
+                ipv4: vec!["192.168.1.20/24".into()],
+                ipv6: Vec::new(),
+                vpn: true,
+            }
+        );
+        assert_eq!(
+            state.wifi,
+            WifiState {
+                enabled: true,
+                access_points: vec![
+                    AccessPoint {
+                        ssid: "Home".into(),
+                        strength: 80,
+                        secured: true,
+                        known: true,
+                        active: true,
+                    },
+                    AccessPoint {
+                        ssid: "Cafe".into(),
+                        strength: 40,
+                        secured: false,
+                        known: false,
+                        active: false,
+                    },
+                    AccessPoint {
+                        ssid: "Office".into(),
+                        strength: 30,
+                        secured: true,
+                        known: false,
+                        active: false,
+                    },
+                    AccessPoint {
+                        ssid: "Campus".into(),
+                        strength: 20,
+                        secured: true,
+                        known: false,
+                        active: false,
+                    },
+                    AccessPoint {
+                        ssid: "Guest".into(),
+                        strength: 10,
+                        secured: false,
+                        known: false,
+                        active: false,
+                    },
+                ],
+                error: None,
+            }
+        );
+    }
+
+    #[test]
+    fn reads_nothing_while_disconnected() {
+        let test_bus = TestBus::start();
+        let disconnected = || {
+            vec![(
+                NM_PATH,
+                NM,
+                props([
+                    ("PrimaryConnection", variant(Path::from("/"))),
+                    ("ActiveConnections", variant(paths(&[]))),
+                    ("Devices", variant(paths(&[]))),
+                    ("WirelessEnabled", variant(false)),
+                ]),
+            )]
+        };
+        let _nm = MockService::start(&test_bus, NM, disconnected, |_| None);
+        let conn = test_bus.bus().connect().unwrap();
+
+        assert_eq!(
+            network_manager().read(&conn, &NetworkState::default()),
+            NetworkState::default()
+        );
+    }
+
+    #[test]
+    fn sets_the_radio_state() {
+        let test_bus = TestBus::start();
+        let (sender, receiver) = channel();
+        let _nm = MockService::start(&test_bus, NM, properties, move |message| {
+            let (interface, property, Variant(enabled)): (String, String, Variant<bool>) =
+                message.read3().ok()?;
+            let _ = sender.send((interface, property, enabled));
+
+            Some(message.method_return())
+        });
+        let conn = test_bus.bus().connect().unwrap();
+
+        network_manager().handle(&conn, &NetworkState::default(), Request::SetEnabled(false));
+
+        assert_eq!(
+            receiver.recv_timeout(TIMEOUT),
+            Ok((NM.to_string(), "WirelessEnabled".to_string(), false))
+        );
+    }
+
+    /// A method call the mock NetworkManager received, with the `key-mgmt` and `psk` of any
+    /// profile it was asked to add.
+    #[derive(Debug, PartialEq)]
+    struct Call {
+        path: String,
+        member: String,
+        security: Option<(String, String)>,
+    }
+
+    impl Call {
+        fn new(path: &str, member: &str) -> Self {
+            Self {
+                path: path.to_string(),
+                member: member.to_string(),
+                security: None,
+            }
+        }
+    }
+
+    /// Starts a mock NetworkManager, and returns the calls it receives besides the settings.
+    fn record_calls(test_bus: &TestBus) -> (MockService, Receiver<Call>) {
+        let (sender, receiver) = channel();
+        let nm = MockService::start(test_bus, NM, properties, move |message| {
+            if let Some(reply) = settings(message) {
+                return Some(reply);
+            }
+
+            let mut call = Call::new(&message.path()?, &message.member()?);
+
+            if &*call.member == "AddAndActivateConnection" {
+                let profile: HashMap<String, PropMap> = message.read1().ok()?;
+                call.security = profile.get("802-11-wireless-security").map(|security| {
+                    let field = |name| prop_cast::<String>(security, name).cloned();
+
+                    (
+                        field("key-mgmt").unwrap_or_default(),
+                        field("psk").unwrap_or_default(),
+                    )
+                });
+            }
+
+            let _ = sender.send(call);
+
+            Some(match message.member().as_deref()? {
+                "ActivateConnection" => message.method_return().append1(Path::from(PRIMARY)),
+                "AddAndActivateConnection" => message
+                    .method_return()
+                    .append2(Path::from(PROFILE), Path::from(PRIMARY)),
+                _ => message.method_return(),
+            })
+        });
+
+        (nm, receiver)
+    }
+
+    fn connect(ssid: &str, passphrase: Option<&str>) -> Request {
+        Request::Connect(ssid.to_string(), passphrase.map(str::to_string))
+    }
+
+    #[test]
+    fn reads_security_from_access_point_flags() {
+        assert_eq!(Security::from_flags(0, 0, 0), Security::Open);
+        assert_eq!(Security::from_flags(1, 0x188, 0), Security::Psk);
+        assert_eq!(Security::from_flags(1, 0, 0x188), Security::Psk);
+        assert_eq!(Security::from_flags(1, 0, 0x588), Security::Psk);
+        assert_eq!(Security::from_flags(1, 0, 0x488), Security::Sae);
+        assert_eq!(Security::from_flags(1, 0, 0x288), Security::Unsupported);
+        assert_eq!(Security::from_flags(1, 0, 0x2000), Security::Unsupported);
+        assert_eq!(Security::from_flags(1, 0, 0), Security::Unsupported);
+        assert_eq!(Security::from_flags(1, 0, 0x808), Security::Open);
+    }
+
+    #[test]
+    fn activates_known_networks_with_their_profile() {
+        let test_bus = TestBus::start();
+        let (_nm, calls) = record_calls(&test_bus);
+        let conn = test_bus.bus().connect().unwrap();
+
+        assert_eq!(handle(&conn, connect("Home", None)), Ok(()));
+        assert_eq!(
+            calls.recv_timeout(TIMEOUT),
+            Ok(Call::new(NM_PATH, "ActivateConnection"))
+        );
+    }
+
+    #[test]
+    fn joins_new_networks_with_matching_key_management() {
+        let test_bus = TestBus::start();
+        let (_nm, calls) = record_calls(&test_bus);
+        let conn = test_bus.bus().connect().unwrap();
+        let joined = |security: Option<(&str, &str)>| {
+            Ok(Call {
+                security: security.map(|(key_mgmt, psk)| (key_mgmt.into(), psk.into())),
+                ..Call::new(NM_PATH, "AddAndActivateConnection")
+            })
+        };
+
+        assert_eq!(handle(&conn, connect("Office", Some("hunter22"))), Ok(()));
+        assert_eq!(
+            calls.recv_timeout(TIMEOUT),
+            joined(Some(("sae", "hunter22")))
+        );
+
+        assert_eq!(handle(&conn, connect("Cafe", Some("ignored"))), Ok(()));
+        assert_eq!(calls.recv_timeout(TIMEOUT), joined(None));
+
+        assert_eq!(handle(&conn, connect("Guest", None)), Ok(()));
+        assert_eq!(calls.recv_timeout(TIMEOUT), joined(None));
+    }
+
+    #[test]
+    fn refuses_networks_it_cannot_join() {
+        let test_bus = TestBus::start();
+        let (_nm, calls) = record_calls(&test_bus);
+        let conn = test_bus.bus().connect().unwrap();
+
+        assert_eq!(
+            handle(&conn, connect("Campus", Some("hunter22"))),
+            Err(WifiError::Unsupported("Campus".into()))
+        );
+        assert_eq!(
+            handle(&conn, connect("Office", None)),
+            Err(WifiError::PassphraseRequired("Office".into()))
+        );
+        assert_eq!(
+            handle(&conn, connect("Library", Some("hunter22"))),
+            Err(WifiError::NotFound("Library".into()))
+        );
+        assert!(calls.try_recv().is_err());
+    }
+
+    #[test]
+    fn disconnects_and_rescans_the_wifi_device() {
+        let test_bus = TestBus::start();
+        let (_nm, calls) = record_calls(&test_bus);
+        let conn = test_bus.bus().connect().unwrap();
+
+        assert_eq!(handle(&conn, Request::Disconnect), Ok(()));
+        assert_eq!(
+            calls.recv_timeout(TIMEOUT),
+            Ok(Call::new(WLAN, "Disconnect"))
+        );
+
+        assert_eq!(handle(&conn, Request::Rescan), Ok(()));
+        assert_eq!(
+            calls.recv_timeout(TIMEOUT),
+            Ok(Call::new(WLAN, "RequestScan"))
+        );
+    }
+
+    #[test]
+    fn reports_failed_requests_until_one_succeeds() {
+        let test_bus = TestBus::start();
+        let (_nm, _calls) = record_calls(&test_bus);
+        let conn = test_bus.bus().connect().unwrap();
+        let mut nm = network_manager();
+
+        nm.handle(&conn, &NetworkState::default(), connect("Library", None));
+        assert!(nm.changed.swap(false, Ordering::AcqRel));
+        assert_eq!(
+            nm.read(&conn, &NetworkState::default()).wifi.error,
+            Some(WifiError::NotFound("Library".into()))
+        );
+
+        nm.handle(&conn, &NetworkState::default(), Request::Rescan);
+        assert!(nm.changed.swap(false, Ordering::AcqRel));
+        assert_eq!(nm.read(&conn, &NetworkState::default()).wifi.error, None);
+    }
+}
@@ -0,0 +1,963 @@
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use dbus::{
+    arg::{prop_cast, PropMap, RefArg},
+    blocking::{stdintf::org_freedesktop_dbus::RequestNameReply, Connection},
+    channel::{MatchingReceiver, Sender as _},
+    message::MatchRule,
+    Message,
+};
+
+use crate::{
+    sources::{
+        bus::{Backend, Service},
+        Bus,
+    },
+    Variable,
+};
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const CAPABILITIES: [&str; 5] = [
+    "actions",
+    "body",
+    "body-markup",
+    "body-hyperlinks",
+    "icon-static",
+];
+
+/// How long a notification stays up if its sender leaves it to the server.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How urgent a notification is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    /// Critical notifications stay up until they are dismissed, unless they set a timeout.
+    Critical,
+}
+
+impl Urgency {
+    pub(crate) fn from_hint(urgency: u8) -> Self {
+        match urgency {
+            0 => Urgency::Low,
+            2 => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+
+    pub(crate) fn hint(&self) -> u8 {
+        match self {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Critical => 2,
+        }
+    }
+
+    /// Returns the CSS class of notifications with this urgency: "low", "normal" or "critical".
+    pub fn class(&self) -> &'static str {
+        match self {
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        }
+    }
+}
+
+/// An image sent as pixels in a notification's `image-data` hint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageData {
+    pub width: i32,
+    pub height: i32,
+    /// The number of bytes between the start of two rows.
+    pub rowstride: i32,
+    /// Whether pixels are 8-bit RGBA rather than RGB.
+    pub has_alpha: bool,
+    pub data: Vec<u8>,
+}
+
+impl ImageData {
+    /// Parses an `(iiibiiay)` image, accepting only 8 bits per sample, as every sender uses.
+    fn parse(image: &dyn RefArg) -> Option<Self> {
+        let mut fields = image.as_iter()?;
+        let mut number = || fields.next()?.as_i64();
+
+        let (width, height, rowstride) = (number()? as i32, number()? as i32, number()? as i32);
+        let (has_alpha, bits, channels) = (number()? != 0, number()?, number()?);
+
+        let data: Vec<u8> = fields
+            .next()?
+            .as_iter()?
+            .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+            .collect();
+
+        let channels_expected = if has_alpha { 4 } else { 3 };
+        let size = (rowstride as i64) * (height as i64 - 1) + width as i64 * channels;
+
+        if width <= 0 || height <= 0 || bits != 8 || channels != channels_expected {
+            return None;
+        }
+
+        (data.len() as i64 >= size).then_some(Self {
+            width,
+            height,
+            rowstride,
+            has_alpha,
+            data,
+        })
+    }
+}
+
+/// The image a notification shows, in the order the specification prefers them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationImage {
+    Data(ImageData),
+    /// A local file path.
+    File(String),
+    /// A themed icon name.
+    Icon(String),
+}
+
+impl NotificationImage {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "" => None,
+            _ if name.starts_with("file://") => {
+                Some(Self::File(name["file://".len()..].to_string()))
+            }
+            _ if name.starts_with('/') => Some(Self::File(name.to_string())),
+            _ => Some(Self::Icon(name.to_string())),
+        }
+    }
+}
+
+/// A button on a notification.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Action {
+    /// Sent back to the application when the action is invoked.
+    pub key: String,
+    pub label: String,
+}
+
+/// A notification received from an application.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Notification {
+    pub id: u32,
+    pub app_name: String,
+    pub app_icon: String,
+    pub summary: String,
+    /// The body, which may contain simple markup and links.
+    pub body: String,
+    /// Actions to show as buttons. The action with the key "default" is invoked by clicking
+    /// the notification itself.
+    pub actions: Vec<Action>,
+    pub urgency: Urgency,
+    pub image: Option<NotificationImage>,
+    pub category: Option<String>,
+    pub desktop_entry: Option<String>,
+    /// Whether the notification should be left out of any history.
+    pub transient: bool,
+    /// Whether the notification stays up after one of its actions is invoked.
+    pub resident: bool,
+    /// How long the notification stays up, or `None` until it is dismissed.
+    pub timeout: Option<Duration>,
+}
+
+impl Notification {
+    /// Returns the action invoked by clicking the notification, if it has one.
+    pub fn default_action(&self) -> Option<&Action> {
+        self.actions.iter().find(|action| action.key == "default")
+    }
+}
+
+/// Why a notification was closed, as sent in the NotificationClosed signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CloseReason {
+    Expired = 1,
+    Dismissed = 2,
+    Closed = 3,
+}
+
+/// Reads the arguments of a Notify call into a notification, along with the id it replaces.
+fn read_notify(message: &Message) -> Option<(u32, Notification)> {
+    let mut args = message.iter_init();
+
+    let app_name: String = args.read().ok()?;
+    let replaces_id: u32 = args.read().ok()?;
+    let app_icon: String = args.read().ok()?;
+    let summary: String = args.read().ok()?;
+    let body: String = args.read().ok()?;
+    let actions: Vec<String> = args.read().ok()?;
+    let hints: PropMap = args.read().ok()?;
+    let expire_timeout: i32 = args.read().ok()?;
+
+    let text = |name: &str| prop_cast::<String>(&hints, name).cloned();
+    let flag = |name: &str| prop_cast::<bool>(&hints, name).copied().unwrap_or(false);
+
+    let urgency = Urgency::from_hint(prop_cast::<u8>(&hints, "urgency").copied().unwrap_or(1));
+
+    // Older senders use the hint names of earlier versions of the specification.
+    let image = ["image-data", "image_data", "icon_data"]
+        .into_iter()
+        .find_map(|name| ImageData::parse(&*hints.get(name)?.0))
+        .map(NotificationImage::Data)
+        .or_else(|| {
+            ["image-path", "image_path"]
+                .into_iter()
+                .find_map(|name| NotificationImage::from_name(&text(name)?))
+        })
+        .or_else(|| NotificationImage::from_name(&app_icon));
+
+    let timeout = match expire_timeout {
+        0 => None,
+        timeout if timeout > 0 => Some(Duration::from_millis(timeout as u64)),
+        _ if urgency == Urgency::Critical => None,
+        _ => Some(DEFAULT_TIMEOUT),
+    };
+
+    let notification = Notification {
+        id: 0,
+        app_name,
+        app_icon,
+        summary,
+        body,
+        actions: actions
+            .chunks_exact(2)
+            .map(|pair| Action {
+                key: pair[0].clone(),
+                label: pair[1].clone(),
+            })
+            .collect(),
+        urgency,
+        image,
+        category: text("category"),
+        desktop_entry: text("desktop-entry"),
+        transient: flag("transient"),
+        resident: flag("resident"),
+        timeout,
+    };
+
+    Some((replaces_id, notification))
+}
+
+/// The notifications currently up, shared between the method handlers and the serve loop.
+#[derive(Default)]
+struct Daemon {
+    notifications: Vec<Notification>,
+    deadlines: HashMap<u32, Instant>,
+    last_id: u32,
+    /// Sent every notification as it arrives, e.g. to keep a history.
+    recorders: Vec<Sender<Notification>>,
+}
+
+type Shared = Arc<Mutex<Daemon>>;
+
+impl Daemon {
+    /// Adds a notification, or replaces the one with `replaces_id` in place. Returns its id.
+    fn notify(&mut self, replaces_id: u32, mut notification: Notification) -> u32 {
+        let existing = self
+            .notifications
+            .iter()
+            .position(|shown| replaces_id != 0 && shown.id == replaces_id);
+
+        notification.id = match existing {
+            Some(_) => replaces_id,
+            None => {
+                // Zero means "no notification" to senders, so it is never handed out.
+                self.last_id = self.last_id.wrapping_add(1).max(1);
+                self.last_id
+            }
+        };
+
+        match notification.timeout {
+            Some(timeout) => self
+                .deadlines
+                .insert(notification.id, Instant::now() + timeout),
+            None => self.deadlines.remove(&notification.id),
+        };
+
+        self.recorders
+            .retain(|recorder| recorder.send(notification.clone()).is_ok());
+
+        let id = notification.id;
+
+        match existing {
+            Some(index) => self.notifications[index] = notification,
+            None => self.notifications.push(notification),
+        }
+
+        id
+    }
+
+    /// Removes a notification. Returns false if it wasn't up.
+    fn close(&mut self, id: u32) -> bool {
+        self.deadlines.remove(&id);
+
+        let count = self.notifications.len();
+        self.notifications
+            .retain(|notification| notification.id != id);
+
+        self.notifications.len() != count
+    }
+
+    /// Removes every notification whose timeout ran out, returning their ids.
+    fn expire(&mut self) -> Vec<u32> {
+        let now = Instant::now();
+        let expired: Vec<u32> = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in &expired {
+            self.close(*id);
+        }
+
+        expired
+    }
+
+    /// Returns when the next notification expires, if any will.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.values().min().copied()
+    }
+
+    fn find(&self, id: u32) -> Option<&Notification> {
+        self.notifications
+            .iter()
+            .find(|notification| notification.id == id)
+    }
+}
+
+fn emit_closed(conn: &Connection, id: u32, reason: CloseReason) {
+    if let Ok(signal) = Message::new_signal(NOTIFICATIONS_PATH, NOTIFICATIONS, "NotificationClosed")
+    {
+        let _ = conn.send(signal.append2(id, reason as u32));
+    }
+}
+
+fn error_reply(message: &Message, name: &str, text: &str) -> Message {
+    message.error(&name.into(), &CString::new(text).unwrap_or_default())
+}
+
+/// Answers a method call to our notification server.
+fn handle_method(message: &Message, conn: &Connection, daemon: &Shared) -> Message {
+    let invalid_args = || {
+        error_reply(
+            message,
+            "org.freedesktop.DBus.Error.InvalidArgs",
+            "Invalid arguments",
+        )
+    };
+
+    match (message.interface().as_deref(), message.member().as_deref()) {
+        (Some(NOTIFICATIONS), Some("Notify")) => {
+            let Some((replaces_id, notification)) = read_notify(message) else {
+                return invalid_args();
+            };
+
+            match daemon.lock() {
+                Ok(mut daemon) => message
+                    .method_return()
+                    .append1(daemon.notify(replaces_id, notification)),
+                Err(_) => error_reply(
+                    message,
+                    "org.freedesktop.DBus.Error.Failed",
+                    "Server failed",
+                ),
+            }
+        }
+        (Some(NOTIFICATIONS), Some("CloseNotification")) => {
+            let Ok(id) = message.read1::<u32>() else {
+                return invalid_args();
+            };
+
+            // Closing a notification that isn't up is not an error.
+            if daemon.lock().is_ok_and(|mut daemon| daemon.close(id)) {
+                emit_closed(conn, id, CloseReason::Closed);
+            }
+
+            message.method_return()
+        }
+        (Some(NOTIFICATIONS), Some("GetCapabilities")) => {
+            message.method_return().append1(CAPABILITIES.to_vec())
+        }
+        (Some(NOTIFICATIONS), Some("GetServerInformation")) => message
+            .method_return()
+            .append2("chunks-rs", "chunks-rs")
+            .append2(env!("CARGO_PKG_VERSION"), "1.2"),
+        _ => error_reply(
+            message,
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            "Unknown method",
+        ),
+    }
+}
+
+enum Request {
+    Dismiss(u32),
+    Invoke(u32, String),
+    /// Sends every notification arriving from now on to the sender.
+    Record(Sender<Notification>),
+}
+
+/// Handles a request from the main thread. Returns true if a notification was closed.
+fn handle(conn: &Connection, daemon: &Shared, request: Request) -> bool {
+    let Ok(mut daemon) = daemon.lock() else {
+        return false;
+    };
+
+    match request {
+        Request::Dismiss(id) => {
+            let closed = daemon.close(id);
+            if closed {
+                emit_closed(conn, id, CloseReason::Dismissed);
+            }
+            closed
+        }
+        Request::Invoke(id, key) => {
+            let Some(resident) = daemon.find(id).map(|notification| notification.resident) else {
+                return false;
+            };
+
+            if let Ok(signal) =
+                Message::new_signal(NOTIFICATIONS_PATH, NOTIFICATIONS, "ActionInvoked")
+            {
+                let _ = conn.send(signal.append2(id, key));
+            }
+
+            if resident {
+                return false;
+            }
+
+            daemon.close(id);
+            emit_closed(conn, id, CloseReason::Dismissed);
+            true
+        }
+        Request::Record(recorder) => {
+            daemon.recorders.push(recorder);
+            false
+        }
+    }
+}
+
+/// The notification server: owns the org.freedesktop.Notifications name, answers its method
+/// calls and expires notifications, reading them again whenever they change.
+struct Server {
+    daemon: Shared,
+    changed: Arc<AtomicBool>,
+}
+
+impl Service for Server {
+    type State = Vec<Notification>;
+    type Request = Request;
+
+    /// Gives up if another server, e.g. mako or dunst, already owns the name.
+    fn connect(conn: &Connection, changed: &Arc<AtomicBool>) -> Option<Self> {
+        match conn.request_name(NOTIFICATIONS, false, false, true) {
+            Ok(RequestNameReply::PrimaryOwner) => (),
+            _ => return None,
+        }
+
+        let daemon: Shared = Arc::new(Mutex::new(Daemon::default()));
+
+        let (shared, flag) = (Arc::clone(&daemon), Arc::clone(changed));
+        conn.start_receive(
+            MatchRule::new_method_call().with_path(NOTIFICATIONS_PATH),
+            Box::new(move |message, conn| {
+                let _ = conn.send(handle_method(&message, conn, &shared));
+                flag.store(true, Ordering::Release);
+                true
+            }),
+        );
+
+        Some(Server {
+            daemon,
+            changed: Arc::clone(changed),
+        })
+    }
+
+    /// Method calls are received as they are, so there is nothing to match.
+    fn rules(&self) -> Vec<MatchRule<'static>> {
+        Vec::new()
+    }
+
+    fn read(&mut self, conn: &Connection, _: &Vec<Notification>) -> Vec<Notification> {
+        let Ok(mut daemon) = self.daemon.lock() else {
+            return Vec::new();
+        };
+
+        for id in daemon.expire() {
+            emit_closed(conn, id, CloseReason::Expired);
+        }
+
+        daemon.notifications.clone()
+    }
+
+    fn handle(&mut self, conn: &Connection, _: &Vec<Notification>, request: Request) {
+        if handle(conn, &self.daemon, request) {
+            self.changed.store(true, Ordering::Release);
+        }
+    }
+
+    fn stale(&self, _: &Vec<Notification>, _: Duration) -> bool {
+        self.next_deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// Wakes up as the next notification expires, so it closes on time.
+    fn interval(&self) -> Duration {
+        self.next_deadline()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .map_or(Self::INTERVAL, |left| left.min(Self::INTERVAL))
+    }
+}
+
+impl Server {
+    fn next_deadline(&self) -> Option<Instant> {
+        self.daemon.lock().ok()?.next_deadline()
+    }
+}
+
+/// A notification server, implementing `org.freedesktop.Notifications` so applications and
+/// `notify-send` can show notifications without mako or dunst.
+///
+/// The server starts once its notifications are first watched, and only runs if no other
+/// server owns the name. Must be used from the GTK main thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notifications {
+    bus: Bus,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifications {
+    /// Serves notifications on the session bus.
+    pub fn new() -> Self {
+        Notifications::on(Bus::Session)
+    }
+
+    /// Serves notifications on the given bus, e.g. a private bus to test with `notify-send`.
+    pub fn on(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    /// Returns a `Variable` with the notifications currently up, oldest first. It updates as
+    /// soon as one arrives, is replaced, expires or is closed.
+    pub fn watch(&self) -> Variable<Vec<Notification>> {
+        Backend::<Server>::on(&self.bus).state()
+    }
+
+    /// Closes a notification on behalf of the user.
+    pub fn dismiss(&self, id: u32) {
+        Backend::<Server>::on(&self.bus).send(Request::Dismiss(id));
+    }
+
+    /// Invokes one of a notification's actions, closing it unless it is resident.
+    pub fn invoke(&self, id: u32, key: &str) {
+        Backend::<Server>::on(&self.bus).send(Request::Invoke(id, key.to_string()));
+    }
+
+    /// Sends every notification arriving from now on to `recorder`, including ones replaced or
+    /// closed before the main loop gets to them.
+    pub(crate) fn record(&self, recorder: Sender<Notification>) {
+        Backend::<Server>::on(&self.bus).send(Request::Record(recorder));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, thread};
+
+    use dbus::blocking::Proxy;
+    use gio::glib::MainContext;
+
+    use super::*;
+    use crate::sources::fixture::{props, variant, MockService, TestBus};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn notify(actions: &[&str], hints: PropMap, expire_timeout: i32) -> Message {
+        let actions: Vec<String> = actions.iter().map(|action| action.to_string()).collect();
+
+        let mut message =
+            Message::new_method_call(NOTIFICATIONS, NOTIFICATIONS_PATH, NOTIFICATIONS, "Notify")
+                .unwrap();
+
+        message.append_all((
+            "app",
+            7u32,
+            "dialog-information",
+            "Summary",
+            "Body",
+            actions,
+            hints,
+            expire_timeout,
+        ));
+        message
+    }
+
+    fn image(bits: i32, data: Vec<u8>) -> (i32, i32, i32, bool, i32, i32, Vec<u8>) {
+        (2, 1, 8, true, bits, 4, data)
+    }
+
+    fn proxy(conn: &Connection) -> Proxy<'_, &Connection> {
+        conn.with_proxy(NOTIFICATIONS, NOTIFICATIONS_PATH, TIMEOUT)
+    }
+
+    #[test]
+    fn maps_urgency_hints() {
+        assert_eq!(Urgency::from_hint(0), Urgency::Low);
+        assert_eq!(Urgency::from_hint(1), Urgency::Normal);
+        assert_eq!(Urgency::from_hint(2), Urgency::Critical);
+        assert_eq!(Urgency::from_hint(9), Urgency::Normal);
+        assert_eq!(Urgency::Critical.hint(), 2);
+    }
+
+    #[test]
+    fn parses_image_data() {
+        assert_eq!(
+            ImageData::parse(&image(8, vec![1, 2, 3, 4, 5, 6, 7, 8])),
+            Some(ImageData {
+                width: 2,
+                height: 1,
+                rowstride: 8,
+                has_alpha: true,
+                data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            })
+        );
+        assert_eq!(ImageData::parse(&image(16, vec![0; 8])), None);
+        assert_eq!(ImageData::parse(&image(8, vec![0; 7])), None);
+        assert_eq!(ImageData::parse(&"image".to_string()), None);
+    }
+
+    #[test]
+    fn tells_image_files_from_icon_names() {
+        assert_eq!(
+            NotificationImage::from_name("file:///tmp/a.png"),
+            Some(NotificationImage::File("/tmp/a.png".to_string()))
+        );
+        assert_eq!(
+            NotificationImage::from_name("/tmp/a.png"),
+            Some(NotificationImage::File("/tmp/a.png".to_string()))
+        );
+        assert_eq!(
+            NotificationImage::from_name("mail-unread"),
+            Some(NotificationImage::Icon("mail-unread".to_string()))
+        );
+        assert_eq!(NotificationImage::from_name(""), None);
+    }
+
+    #[test]
+    fn reads_notify_arguments_and_hints() {
+        let hints = props([
+            ("urgency", variant(0u8)),
+            ("image-path", variant("file:///tmp/a.png".to_string())),
+            ("category", variant("email.arrived".to_string())),
+            ("desktop-entry", variant("thunderbird".to_string())),
+            ("transient", variant(true)),
+            ("resident", variant(true)),
+        ]);
+
+        let (replaces_id, notification) = read_notify(&notify(
+            &["default", "Open", "reply", "Reply", "odd"],
+            hints,
+            3000,
+        ))
+        .unwrap();
+
+        assert_eq!(replaces_id, 7);
+        assert_eq!(
+            notification,
+            Notification {
+                id: 0,
+                app_name: "app".to_string(),
+                app_icon: "dialog-information".to_string(),
+                summary: "Summary".to_string(),
+                body: "Body".to_string(),
+                actions: vec![
+                    Action {
+                        key: "default".to_string(),
+                        label: "Open".to_string(),
+                    },
+                    Action {
+                        key: "reply".to_string(),
+                        label: "Reply".to_string(),
+                    },
+                ],
+                urgency: Urgency::Low,
+                image: Some(NotificationImage::File("/tmp/a.png".to_string())),
+                category: Some("email.arrived".to_string()),
+                desktop_entry: Some("thunderbird".to_string()),
+                transient: true,
+                resident: true,
+                timeout: Some(Duration::from_millis(3000)),
+            }
+        );
+        assert_eq!(
+            notification
+                .default_action()
+                .map(|action| action.label.as_str()),
+            Some("Open")
+        );
+    }
+
+    #[test]
+    fn prefers_image_data_over_paths_and_app_icons() {
+        let hints = props([
+            ("image_data", variant(image(8, vec![0; 8]))),
+            ("image-path", variant("/tmp/a.png".to_string())),
+        ]);
+        let (_, notification) = read_notify(&notify(&[], hints, -1)).unwrap();
+
+        assert!(matches!(
+            notification.image,
+            Some(NotificationImage::Data(_))
+        ));
+
+        let (_, notification) = read_notify(&notify(&[], PropMap::new(), -1)).unwrap();
+
+        assert_eq!(
+            notification.image,
+            Some(NotificationImage::Icon("dialog-information".to_string()))
+        );
+    }
+
+    #[test]
+    fn picks_timeouts_by_urgency() {
+        let timeout = |urgency: u8, expire_timeout: i32| {
+            let hints = props([("urgency", variant(urgency))]);
+
+            read_notify(&notify(&[], hints, expire_timeout))
+                .unwrap()
+                .1
+                .timeout
+        };
+
+        assert_eq!(timeout(1, -1), Some(DEFAULT_TIMEOUT));
+        assert_eq!(timeout(1, 0), None);
+        assert_eq!(timeout(1, 1500), Some(Duration::from_millis(1500)));
+        assert_eq!(timeout(2, -1), None);
+        assert_eq!(timeout(2, 1500), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn rejects_malformed_notify_calls() {
+        let message =
+            Message::new_method_call(NOTIFICATIONS, NOTIFICATIONS_PATH, NOTIFICATIONS, "Notify")
+                .unwrap()
+                .append2("app", 0u32);
+
+        assert_eq!(read_notify(&message), None);
+    }
+
+    #[test]
+    fn replaces_and_closes_notifications() {
+        let mut daemon = Daemon::default();
+        let notification = |summary: &str| Notification {
+            summary: summary.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(daemon.notify(0, notification("First")), 1);
+        assert_eq!(daemon.notify(0, notification("Second")), 2);
+        assert_eq!(daemon.notify(1, notification("Replaced")), 1);
+        assert_eq!(daemon.notify(9, notification("Third")), 3);
+
+        assert_eq!(
+            daemon
+                .notifications
+                .iter()
+                .map(|notification| (notification.id, notification.summary.as_str()))
+                .collect::<Vec<_>>(),
+            [(1, "Replaced"), (2, "Second"), (3, "Third")]
+        );
+
+        assert!(daemon.close(2));
+        assert!(!daemon.close(2));
+        assert!(daemon.find(2).is_none());
+    }
+
+    /// Iterates a main context until `done` returns true, or panics after a few seconds.
+    fn iterate_until(context: &MainContext, done: impl Fn() -> bool) {
+        let started = Instant::now();
+
+        while !done() {
+            assert!(started.elapsed() < TIMEOUT, "timed out");
+
+            context.iteration(false);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn call_notify(
+        conn: &Connection,
+        replaces_id: u32,
+        summary: &str,
+        urgency: u8,
+        expire_timeout: i32,
+    ) -> u32 {
+        let hints = props([("urgency", variant(urgency))]);
+        let (id,): (u32,) = proxy(conn)
+            .method_call(
+                NOTIFICATIONS,
+                "Notify",
+                (
+                    "app",
+                    replaces_id,
+                    "",
+                    summary,
+                    "",
+                    Vec::<String>::new(),
+                    hints,
+                    expire_timeout,
+                ),
+            )
+            .unwrap();
+
+        id
+    }
+
+    #[test]
+    fn serves_notify_and_close_calls() {
+        let test_bus = TestBus::start();
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let shown = Notifications::on(test_bus.bus()).watch();
+                let conn = test_bus.bus().connect().unwrap();
+                test_bus.wait_for(NOTIFICATIONS);
+
+                assert_eq!(call_notify(&conn, 0, "Hello", 2, -1), 1);
+                iterate_until(&context, || shown.get().len() == 1);
+
+                let notification = &shown.get()[0];
+                assert_eq!(notification.id, 1);
+                assert_eq!(notification.summary, "Hello");
+                assert_eq!(notification.urgency, Urgency::Critical);
+                assert_eq!(notification.timeout, None);
+
+                assert_eq!(call_notify(&conn, 1, "Updated", 1, 0), 1);
+                iterate_until(&context, || shown.get()[0].summary == "Updated");
+                assert_eq!(shown.get().len(), 1);
+
+                let _: () = proxy(&conn)
+                    .method_call(NOTIFICATIONS, "CloseNotification", (1u32,))
+                    .unwrap();
+
+                iterate_until(&context, || shown.get().is_empty());
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn expires_notifications_on_time() {
+        let test_bus = TestBus::start();
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let shown = Notifications::on(test_bus.bus()).watch();
+                let conn = test_bus.bus().connect().unwrap();
+                test_bus.wait_for(NOTIFICATIONS);
+
+                let sent = Instant::now();
+                call_notify(&conn, 0, "Brief", 1, 200);
+                call_notify(&conn, 0, "Lasting", 1, 0);
+
+                iterate_until(&context, || shown.get().len() == 2);
+                iterate_until(&context, || shown.get().len() == 1);
+
+                // Well within the minute the server otherwise sleeps for.
+                assert!(sent.elapsed() < Duration::from_secs(2));
+                assert_eq!(shown.get()[0].summary, "Lasting");
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn answers_malformed_calls_with_invalid_args() {
+        let test_bus = TestBus::start();
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let _shown = Notifications::on(test_bus.bus()).watch();
+                let conn = test_bus.bus().connect().unwrap();
+                test_bus.wait_for(NOTIFICATIONS);
+
+                let notify: Result<(u32,), dbus::Error> =
+                    proxy(&conn).method_call(NOTIFICATIONS, "Notify", ("app", 0u32));
+                let close: Result<(), dbus::Error> =
+                    proxy(&conn).method_call(NOTIFICATIONS, "CloseNotification", ("one",));
+                let unknown: Result<(), dbus::Error> =
+                    proxy(&conn).method_call(NOTIFICATIONS, "Frobnicate", ());
+
+                assert_eq!(
+                    notify.unwrap_err().name(),
+                    Some("org.freedesktop.DBus.Error.InvalidArgs")
+                );
+                assert_eq!(
+                    close.unwrap_err().name(),
+                    Some("org.freedesktop.DBus.Error.InvalidArgs")
+                );
+                assert_eq!(
+                    unknown.unwrap_err().name(),
+                    Some("org.freedesktop.DBus.Error.UnknownMethod")
+                );
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn sends_every_notification_to_recorders() {
+        let mut daemon = Daemon::default();
+        let (recorder, recorded) = channel();
+        let notification = |summary: &str| Notification {
+            summary: summary.to_string(),
+            ..Default::default()
+        };
+
+        daemon.recorders.push(recorder);
+        daemon.notify(0, notification("First"));
+        daemon.notify(1, notification("Replaced"));
+        daemon.close(1);
+
+        assert_eq!(
+            recorded
+                .try_iter()
+                .map(|notification| (notification.id, notification.summary))
+                .collect::<Vec<_>>(),
+            [(1, "First".to_string()), (1, "Replaced".to_string())]
+        );
+
+        drop(recorded);
+        daemon.notify(0, notification("Unheard"));
+        assert!(daemon.recorders.is_empty());
+    }
+
+    #[test]
+    fn leaves_the_name_to_another_server() {
+        let test_bus = TestBus::start();
+        let _dunst = MockService::start(&test_bus, NOTIFICATIONS, Vec::new, |_| None);
+        let conn = test_bus.bus().connect().unwrap();
+
+        assert!(Server::connect(&conn, &Arc::new(AtomicBool::new(false))).is_none());
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+
+use sysinfo::{DiskExt, System, SystemExt};
+
+use crate::{scheduler::Interval, Variable};
+
+/// Filesystems that don't live on a disk, and are left out unless asked for by name.
+const VIRTUAL_FILE_SYSTEMS: [&str; 9] = [
+    "tmpfs",
+    "devtmpfs",
+    "ramfs",
+    "squashfs",
+    "overlay",
+    "efivarfs",
+    "fuse.portal",
+    "fuse.gvfsd-fuse",
+    "autofs",
+];
+
+/// A mounted filesystem, with its space in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub file_system: String,
+    pub removable: bool,
+    pub total: u64,
+    pub free: u64,
+    pub used: u64,
+}
+
+/// Space used on one or more filesystems, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub total: u64,
+    pub free: u64,
+    pub used: u64,
+    /// Used space as a percentage of the total, from 0 to 100.
+    pub percent: f64,
+}
+
+/// Reasons storage usage can't be reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// No mounted filesystem matched the filters.
+    NoMounts,
+    /// The matching filesystems report no space at all.
+    Empty,
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NoMounts => write!(f, "no mounted filesystem matches the filters"),
+            StorageError::Empty => write!(f, "the matching filesystems have no space"),
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+/// Reports the space used on mounted filesystems.
+///
+/// By default, every filesystem backed by a non-removable disk is included, and filesystems
+/// mounted more than once (e.g. btrfs subvolumes) are only counted once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Storage {
+    file_systems: Vec<String>,
+    mount_points: Vec<PathBuf>,
+    removable: bool,
+}
+
+impl Storage {
+    /// Includes every filesystem on a non-removable disk.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only includes filesystems of the given types, e.g. `["ext4", "btrfs"]`. Virtual
+    /// filesystems such as tmpfs are included when named here.
+    pub fn file_systems(mut self, file_systems: &[&str]) -> Self {
+        self.file_systems = file_systems.iter().map(|fs| fs.to_string()).collect();
+        self
+    }
+
+    /// Only includes the filesystems mounted at the given paths, e.g. `["/", "/home"]`.
+    pub fn mount_points(mut self, mount_points: &[&str]) -> Self {
+        self.mount_points = mount_points.iter().map(PathBuf::from).collect();
+        self
+    }
+
+    /// Includes removable media, such as USB sticks.
+    pub fn removable(mut self, removable: bool) -> Self {
+        self.removable = removable;
+        self
+    }
+
+    fn matches(&self, mount: &Mount) -> bool {
+        let file_system = if self.file_systems.is_empty() {
+            !VIRTUAL_FILE_SYSTEMS.contains(&mount.file_system.as_str())
+        } else {
+            self.file_systems.contains(&mount.file_system)
+        };
+
+        let mount_point =
+            self.mount_points.is_empty() || self.mount_points.contains(&mount.mount_point);
+
+        let removable = self.removable || !mount.removable || !self.mount_points.is_empty();
+
+        file_system && mount_point && removable
+    }
+
+    /// Returns every mounted filesystem that matches the filters.
+    pub fn mounts(&self) -> Vec<Mount> {
+        let mut system = System::new();
+        system.refresh_disks_list();
+
+        let mounts = system.disks().iter().map(|disk| {
+            let (total, free) = (disk.total_space(), disk.available_space());
+
+            Mount {
+                device: disk.name().to_string_lossy().to_string(),
+                mount_point: disk.mount_point().to_path_buf(),
+                file_system: String::from_utf8_lossy(disk.file_system()).to_string(),
+                removable: disk.is_removable(),
+                total,
+                free,
+                used: total.saturating_sub(free),
+            }
+        });
+
+        self.select(mounts)
+    }
+
+    /// Keeps the mounts that match the filters, counting each device only once.
+    fn select(&self, mounts: impl IntoIterator<Item = Mount>) -> Vec<Mount> {
+        let mut devices = HashSet::new();
+
+        mounts
+            .into_iter()
+            .filter(|mount| self.matches(mount))
+            .filter(|mount| devices.insert(mount.device.clone()))
+            .collect()
+    }
+
+    /// Returns the combined usage of every matching filesystem.
+    pub fn usage(&self) -> Result<Usage, StorageError> {
+        combine(&self.mounts())
+    }
+
+    /// Returns a `Variable` with the combined usage, read on a worker thread at a given interval
+    /// so that slow or hung mounts don't freeze the UI.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(self, interval: impl Into<Interval>) -> Variable<Result<Usage, StorageError>> {
+        Variable::poll_threaded(Err(StorageError::NoMounts), interval, 10, move || {
+            self.usage()
+        })
+    }
+}
+
+/// Adds up the space of the given mounts.
+fn combine(mounts: &[Mount]) -> Result<Usage, StorageError> {
+    if mounts.is_empty() {
+        return Err(StorageError::NoMounts);
+    }
+
+    let total: u64 = mounts.iter().map(|mount| mount.total).sum();
+    let free: u64 = mounts.iter().map(|mount| mount.free).sum();
+
+    if total == 0 {
+        return Err(StorageError::Empty);
+    }
+
+    let used = total.saturating_sub(free);
+
+    Ok(Usage {
+        total,
+        free,
+        used,
+        percent: used as f64 / total as f64 * 100.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn mount(device: &str, mount_point: &str, file_system: &str, removable: bool) -> Mount {
+        Mount {
+            device: device.to_string(),
+            mount_point: PathBuf::from(mount_point),
+            file_system: file_system.to_string(),
+            removable,
+            total: 100,
+            free: 25,
+            used: 75,
+        }
+    }
+
+    #[test]
+    fn skips_virtual_file_systems_unless_named() {
+        let tmp = mount("tmpfs", "/tmp", "tmpfs", false);
+        let snap = mount("/dev/loop0", "/snap/core/1", "squashfs", false);
+        let root = mount("/dev/nvme0n1p2", "/", "ext4", false);
+
+        assert!(!Storage::new().matches(&tmp));
+        assert!(!Storage::new().matches(&snap));
+        assert!(Storage::new().matches(&root));
+
+        let tmpfs = Storage::new().file_systems(&["tmpfs"]);
+        assert!(tmpfs.matches(&tmp));
+        assert!(!tmpfs.matches(&root));
+    }
+
+    #[test]
+    fn skips_removable_media_unless_asked() {
+        let stick = mount("/dev/sdb1", "/run/media/usb", "vfat", true);
+
+        assert!(!Storage::new().matches(&stick));
+        assert!(Storage::new().removable(true).matches(&stick));
+    }
+
+    #[test]
+    fn includes_removable_media_mounted_at_a_given_path() {
+        let stick = mount("/dev/sdb1", "/run/media/usb", "vfat", true);
+        let root = mount("/dev/nvme0n1p2", "/", "ext4", false);
+        let storage = Storage::new().mount_points(&["/run/media/usb"]);
+
+        assert!(storage.matches(&stick));
+        assert!(!storage.matches(&root));
+    }
+
+    #[test]
+    fn counts_devices_mounted_more_than_once_once() {
+        let mounts = [
+            mount("/dev/nvme0n1p2", "/", "btrfs", false),
+            mount("/dev/nvme0n1p2", "/home", "btrfs", false),
+            mount("/dev/sda1", "/data", "ext4", false),
+            mount("tmpfs", "/tmp", "tmpfs", false),
+        ];
+
+        let selected = Storage::new().select(mounts);
+        let mount_points: Vec<_> = selected.iter().map(|mount| &mount.mount_point).collect();
+        assert_eq!(mount_points, [Path::new("/"), Path::new("/data")]);
+
+        assert_eq!(
+            combine(&selected),
+            Ok(Usage {
+                total: 200,
+                free: 50,
+                used: 150,
+                percent: 75.0,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_errors_instead_of_a_percentage() {
+        assert_eq!(combine(&[]), Err(StorageError::NoMounts));
+
+        let empty = Mount {
+            total: 0,
+            free: 0,
+            used: 0,
+            ..mount("/dev/sr0", "/media/cdrom", "iso9660", true)
+        };
+        assert_eq!(combine(&[empty]), Err(StorageError::Empty));
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use sysinfo::{ComponentExt, CpuExt, System, SystemExt};
+
+use crate::{scheduler::Interval, Variable};
+
+/// Readings taken within this long of each other are shared, so subscribers polling at
+/// different intervals don't refresh the same category twice in a row. This is also about the
+/// shortest span sysinfo can measure CPU usage over.
+const FRESH: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Category {
+    Cpu,
+    Memory,
+    Temperature,
+}
+
+/// The single `System` every source in this module reads from. Only the categories being read
+/// are refreshed, so e.g. hwmon sensors are never scanned unless something shows a temperature.
+struct Monitor {
+    system: System,
+    refreshed: HashMap<Category, Instant>,
+}
+
+thread_local! {
+    static MONITOR: RefCell<Monitor> = RefCell::new(Monitor {
+        system: System::new(),
+        refreshed: HashMap::new(),
+    });
+}
+
+impl Monitor {
+    fn refresh(&mut self, category: Category) {
+        let now = Instant::now();
+
+        if self
+            .refreshed
+            .get(&category)
+            .is_some_and(|last| now.duration_since(*last) < FRESH)
+        {
+            return;
+        }
+
+        match category {
+            Category::Cpu => self.system.refresh_cpu(),
+            Category::Memory => self.system.refresh_memory(),
+            Category::Temperature if self.system.components().is_empty() => {
+                self.system.refresh_components_list()
+            }
+            Category::Temperature => self.system.refresh_components(),
+        }
+
+        self.refreshed.insert(category, now);
+    }
+
+    /// Refreshes a category unless it is already fresh, then reads from it.
+    fn read<R>(category: Category, read_fn: impl FnOnce(&System) -> R) -> R {
+        MONITOR.with(|monitor| {
+            let mut monitor = monitor.borrow_mut();
+            monitor.refresh(category);
+
+            read_fn(&monitor.system)
+        })
+    }
+}
+
+/// CPU usage in percent, from 0 to 100.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuUsage {
+    pub total: f32,
+    pub cores: Vec<f32>,
+}
+
+/// Memory and swap, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+}
+
+impl MemoryUsage {
+    /// Used memory as a percentage of the total, from 0 to 100.
+    pub fn percent(&self) -> f64 {
+        percent(self.used, self.total)
+    }
+
+    /// Used swap as a percentage of the total, from 0 to 100. Zero without swap.
+    pub fn swap_percent(&self) -> f64 {
+        percent(self.swap_used, self.swap_total)
+    }
+}
+
+fn percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64 * 100.0
+    }
+}
+
+/// The system load average over the last 1, 5 and 15 minutes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+/// A hwmon temperature sensor, in degrees Celsius.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Temperature {
+    /// The sensor's label, e.g. "coretemp Package id 0" or "amdgpu edge".
+    pub label: String,
+    pub current: f32,
+    /// The highest temperature seen since the sensor was first read.
+    pub max: f32,
+    pub critical: Option<f32>,
+}
+
+/// CPU usage, shared with every other source in this module.
+pub struct Cpu;
+
+impl Cpu {
+    /// Returns a `Variable` with the total and per-core CPU usage, measured since the previous
+    /// reading. The first reading is always zero, as there is nothing to compare against yet.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(interval: impl Into<Interval>) -> Variable<CpuUsage> {
+        Variable::poll(interval, || {
+            Monitor::read(Category::Cpu, |system| CpuUsage {
+                total: system.global_cpu_info().cpu_usage(),
+                cores: system.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+            })
+        })
+    }
+}
+
+/// Memory and swap usage, shared with every other source in this module.
+pub struct Memory;
+
+impl Memory {
+    /// Returns a `Variable` with the memory and swap usage.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(interval: impl Into<Interval>) -> Variable<MemoryUsage> {
+        Variable::poll(interval, || {
+            Monitor::read(Category::Memory, |system| MemoryUsage {
+                total: system.total_memory(),
+                used: system.used_memory(),
+                available: system.available_memory(),
+                swap_total: system.total_swap(),
+                swap_used: system.used_swap(),
+            })
+        })
+    }
+}
+
+/// The system load average.
+pub struct Load;
+
+impl Load {
+    /// Returns a `Variable` with the load average.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(interval: impl Into<Interval>) -> Variable<LoadAverage> {
+        Variable::poll(interval, || {
+            MONITOR.with(|monitor| {
+                // Read straight from /proc/loadavg, so there is nothing to refresh.
+                let load = monitor.borrow().system.load_average();
+
+                LoadAverage {
+                    one: load.one,
+                    five: load.five,
+                    fifteen: load.fifteen,
+                }
+            })
+        })
+    }
+}
+
+/// hwmon temperature sensors, shared with every other source in this module.
+pub struct Temperatures;
+
+impl Temperatures {
+    /// Returns a `Variable` with every temperature sensor.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(interval: impl Into<Interval>) -> Variable<Vec<Temperature>> {
+        Variable::poll(interval, || {
+            Monitor::read(Category::Temperature, |system| {
+                system
+                    .components()
+                    .iter()
+                    .map(|component| Temperature {
+                        label: component.label().to_string(),
+                        current: component.temperature(),
+                        max: component.max(),
+                        critical: component.critical(),
+                    })
+                    .collect()
+            })
+        })
+    }
+
+    /// Returns a `Variable` with the first sensor whose label contains `label`, e.g. "Package"
+    /// or "Tctl", or `None` if there is no such sensor.
+    pub fn watch_sensor(
+        interval: impl Into<Interval>,
+        label: &str,
+    ) -> Variable<Option<Temperature>> {
+        let label = label.to_string();
+
+        Temperatures::watch(interval).map(move |sensors| {
+            sensors
+                .iter()
+                .find(|sensor| sensor.label.contains(&label))
+                .cloned()
+        })
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    cell::RefCell,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{scheduler::Interval, Variable};
+
+// RTF_UP, from linux/route.h
+const ROUTE_UP: u32 = 0x1;
+
+/// Transfer rates of a network interface, in bytes per second.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rates {
+    /// The interface the rates were measured on. Empty if no interface could be found.
+    pub interface: String,
+    /// Bytes received per second.
+    pub rx: f64,
+    /// Bytes transmitted per second.
+    pub tx: f64,
+}
+
+impl Rates {
+    /// Returns the receive rate in a human-readable form, e.g. "1.2 MB/s".
+    pub fn rx_human(&self) -> String {
+        format_rate(self.rx)
+    }
+
+    /// Returns the transmit rate in a human-readable form, e.g. "340 KB/s".
+    pub fn tx_human(&self) -> String {
+        format_rate(self.tx)
+    }
+}
+
+/// Formats a rate in bytes per second with a decimal unit, e.g. "1.2 MB/s" or "512 B/s".
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    const UNITS: [&str; 5] = ["B/s", "KB/s", "MB/s", "GB/s", "TB/s"];
+
+    let mut rate = bytes_per_sec.max(0.0);
+    let mut unit = 0;
+
+    while rate >= 1000.0 && unit < UNITS.len() - 1 {
+        rate /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 || rate >= 100.0 {
+        format!("{:.0} {}", rate, UNITS[unit])
+    } else {
+        format!("{:.1} {}", rate, UNITS[unit])
+    }
+}
+
+/// Returns the interface of the default route with the lowest metric.
+fn default_interface(root: &Path) -> io::Result<Option<String>> {
+    let routes = fs::read_to_string(root.join("proc/net/route"))?;
+
+    let interface = routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (interface, destination, flags, metric) = (
+                fields.first()?,
+                fields.get(1)?,
+                fields.get(3)?,
+                fields.get(6)?,
+            );
+
+            let flags = u32::from_str_radix(flags, 16).ok()?;
+
+            (*destination == "00000000" && flags & ROUTE_UP != 0).then(|| {
+                (
+                    metric.parse::<u32>().unwrap_or(u32::MAX),
+                    interface.to_string(),
+                )
+            })
+        })
+        .min()
+        .map(|(_, interface)| interface);
+
+    Ok(interface)
+}
+
+/// Returns the received and transmitted byte counters of an interface.
+fn counters(root: &Path, interface: &str) -> io::Result<Option<(u64, u64)>> {
+    let devices = fs::read_to_string(root.join("proc/net/dev"))?;
+
+    // The first two lines are headers, then each line is "iface: rx_bytes ... tx_bytes ...",
+    // where tx_bytes is the ninth counter.
+    let counters = devices.lines().skip(2).find_map(|line| {
+        let (name, stats) = line.split_once(':')?;
+
+        if name.trim() != interface {
+            return None;
+        }
+
+        let stats: Vec<u64> = stats
+            .split_whitespace()
+            .map(|stat| stat.parse().unwrap_or(0))
+            .collect();
+
+        Some((*stats.first()?, *stats.get(8)?))
+    });
+
+    Ok(counters)
+}
+
+/// Follows the throughput of a network interface through `/proc/net/dev`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Throughput {
+    root: PathBuf,
+    interface: Option<String>,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Throughput {
+    /// Follows the interface of the default route, which is looked up again on every poll so
+    /// that switching networks is picked up.
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            interface: None,
+        }
+    }
+
+    /// Follows the given interface instead of the default route's.
+    pub fn interface(mut self, interface: &str) -> Self {
+        self.interface = Some(interface.to_string());
+        self
+    }
+
+    /// Reads `proc/net/dev` and `proc/net/route` relative to the given directory instead of `/`,
+    /// e.g. to test against fixture files.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    fn read(&self) -> io::Result<Option<(String, u64, u64)>> {
+        let interface = match &self.interface {
+            Some(interface) => Some(interface.clone()),
+            None => default_interface(&self.root)?,
+        };
+
+        let Some(interface) = interface else {
+            return Ok(None);
+        };
+
+        Ok(counters(&self.root, &interface)?.map(|(rx, tx)| (interface, rx, tx)))
+    }
+
+    /// Returns a `Variable` with the rates, measured over each interval. The first value is
+    /// always zero, as there is nothing to compare against yet.
+    ///
+    /// Must be called from the GTK main thread.
+    pub fn watch(self, interval: impl Into<Interval>) -> Variable<Rates> {
+        let previous: RefCell<Option<(Instant, String, u64, u64)>> = RefCell::new(None);
+
+        Variable::poll(interval, move || {
+            let Ok(Some((interface, rx, tx))) = self.read() else {
+                previous.replace(None);
+                return Rates::default();
+            };
+
+            let now = Instant::now();
+            let last = previous.replace(Some((now, interface.clone(), rx, tx)));
+
+            match last {
+                // Counters restart when the interface changes or is brought back up.
+                Some((then, last_interface, last_rx, last_tx)) if last_interface == interface => {
+                    let elapsed = now.duration_since(then).as_secs_f64().max(f64::EPSILON);
+
+                    Rates {
+                        interface,
+                        rx: rx.saturating_sub(last_rx) as f64 / elapsed,
+                        tx: tx.saturating_sub(last_tx) as f64 / elapsed,
+                    }
+                }
+                _ => Rates {
+                    interface,
+                    ..Default::default()
+                },
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::fixture::Fixture;
+
+    const ROUTE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+tun0\t00000000\t00000000\t0000\t0\t0\t0\t00000000\t0\t0\t0
+";
+
+    const DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  123456     100    0    0    0     0          0         0   123456     100    0    0    0     0       0          0
+ wlan0: 987654321  50000    0    0    0     0          0         0 12345678   40000    0    0    0     0       0          0
+  eth0: 5000     20    0    0    0     0          0         0 7000     30    0    0    0     0       0          0
+";
+
+    /// The routing table and interface counters, under `/proc/net`.
+    const PROC: &[(&str, &str)] = &[("proc/net/route", ROUTE), ("proc/net/dev", DEV)];
+
+    #[test]
+    fn formats_rates_with_decimal_units() {
+        assert_eq!(format_rate(0.0), "0 B/s");
+        assert_eq!(format_rate(512.0), "512 B/s");
+        assert_eq!(format_rate(1234.0), "1.2 KB/s");
+        assert_eq!(format_rate(340_000.0), "340 KB/s");
+        assert_eq!(format_rate(1_200_000.0), "1.2 MB/s");
+        assert_eq!(format_rate(-5.0), "0 B/s");
+        assert_eq!(format_rate(2e16), "20000 TB/s");
+    }
+
+    #[test]
+    fn picks_the_default_route_with_the_lowest_metric() {
+        let fixture = Fixture::with_files("throughput-route", PROC);
+
+        assert_eq!(
+            default_interface(fixture.path()).unwrap(),
+            Some("eth0".to_string())
+        );
+    }
+
+    #[test]
+    fn finds_no_default_route_without_one() {
+        let fixture = Fixture::new("throughput-no-route");
+        fixture.file("proc/net/route", ROUTE.lines().next().unwrap());
+
+        assert_eq!(default_interface(fixture.path()).unwrap(), None);
+        assert!(default_interface(&fixture.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn reads_the_counters_of_an_interface() {
+        let fixture = Fixture::with_files("throughput-counters", PROC);
+
+        assert_eq!(
+            counters(fixture.path(), "wlan0").unwrap(),
+            Some((987654321, 12345678))
+        );
+        assert_eq!(
+            counters(fixture.path(), "lo").unwrap(),
+            Some((123456, 123456))
+        );
+        assert_eq!(counters(fixture.path(), "wlan1").unwrap(), None);
+    }
+
+    #[test]
+    fn reads_the_default_or_given_interface() {
+        let fixture = Fixture::with_files("throughput-read", PROC);
+
+        assert_eq!(
+            Throughput::new().root(fixture.path()).read().unwrap(),
+            Some(("eth0".to_string(), 5000, 7000))
+        );
+        assert_eq!(
+            Throughput::new()
+                .root(fixture.path())
+                .interface("wlan0")
+                .read()
+                .unwrap(),
+            Some(("wlan0".to_string(), 987654321, 12345678))
+        );
+    }
+
+    #[test]
+    fn formats_rates_for_display() {
+        let rates = Rates {
+            interface: "eth0".to_string(),
+            rx: 1_500_000.0,
+            tx: 340_000.0,
+        };
+
+        assert_eq!(rates.rx_human(), "1.5 MB/s");
+        assert_eq!(rates.tx_human(), "340 KB/s");
+    }
+}
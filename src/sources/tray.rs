@@ -0,0 +1,755 @@
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    blocking::{
+        stdintf::org_freedesktop_dbus::{Properties, RequestNameReply},
+        Connection,
+    },
+    channel::{MatchingReceiver, Sender as _},
+    message::{MatchRule, MessageType},
+    Message, Path,
+};
+use gio::glib::MainContext;
+
+use crate::{
+    sources::{
+        bus::{Backend, Service},
+        Bus,
+    },
+    Variable,
+};
+
+const DBUS: &str = "org.freedesktop.DBus";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+const ITEM: &str = "org.kde.StatusNotifierItem";
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const DBUSMENU: &str = "com.canonical.dbusmenu";
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether a tray item wants to be seen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrayStatus {
+    /// Nothing interesting is going on, and hosts may hide the item.
+    Passive,
+    #[default]
+    Active,
+    /// The item asks for attention, e.g. a new message.
+    NeedsAttention,
+}
+
+impl TrayStatus {
+    fn from_sni(status: &str) -> Self {
+        match status {
+            "Passive" => TrayStatus::Passive,
+            "NeedsAttention" => TrayStatus::NeedsAttention,
+            _ => TrayStatus::Active,
+        }
+    }
+}
+
+/// An icon sent as pixels, for items without a themed icon.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pixmap {
+    pub width: i32,
+    pub height: i32,
+    /// Pixels as 8-bit RGBA, row by row.
+    pub rgba: Vec<u8>,
+}
+
+impl Pixmap {
+    /// Picks the largest of the pixmaps an item sends, converting it from ARGB32 in network
+    /// byte order.
+    fn largest(pixmaps: Vec<(i32, i32, Vec<u8>)>) -> Option<Self> {
+        let (width, height, argb) = pixmaps
+            .into_iter()
+            .filter(|(width, height, data)| {
+                let size = usize::try_from(*width)
+                    .ok()
+                    .zip(usize::try_from(*height).ok())
+                    .and_then(|(width, height)| width.checked_mul(height)?.checked_mul(4));
+
+                *width > 0 && *height > 0 && size == Some(data.len())
+            })
+            .max_by_key(|(width, _, _)| *width)?;
+
+        let rgba = argb
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[1], pixel[2], pixel[3], pixel[0]])
+            .collect();
+
+        Some(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+}
+
+/// An application's icon in the system tray.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayItem {
+    /// Identifies the item while it is registered, as its bus name followed by its path.
+    pub id: String,
+    /// The application's name for the item, e.g. "nextcloud".
+    pub name: String,
+    pub title: String,
+    pub status: TrayStatus,
+    pub icon_name: Option<String>,
+    pub icon_pixmap: Option<Pixmap>,
+    pub attention_icon_name: Option<String>,
+    pub attention_icon_pixmap: Option<Pixmap>,
+    /// An extra directory to look up the item's icon names in.
+    pub icon_theme_path: Option<String>,
+    /// The tooltip's title and description. The description may contain markup.
+    pub tooltip: Option<(String, String)>,
+    /// Whether the item only has a menu, and should open it on left click too.
+    pub item_is_menu: bool,
+    service: String,
+    path: String,
+    menu: Option<Path<'static>>,
+}
+
+impl TrayItem {
+    /// Returns true if the item has a context menu to show.
+    pub fn has_menu(&self) -> bool {
+        self.menu.is_some()
+    }
+}
+
+/// An entry of a tray item's context menu.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MenuItem {
+    pub id: i32,
+    /// The entry's text, with an underscore before its mnemonic.
+    pub label: String,
+    pub enabled: bool,
+    pub visible: bool,
+    pub separator: bool,
+    /// The state of a checkbox or radio button, if the entry has one.
+    pub toggled: Option<bool>,
+    /// Entries of the submenu this entry opens.
+    pub children: Vec<MenuItem>,
+}
+
+/// Splits a registered item into its bus name and path.
+fn split_item(item: &str) -> (String, String) {
+    match item.find('/') {
+        Some(index) => (item[..index].to_string(), item[index..].to_string()),
+        None => (item.to_string(), ITEM_PATH.to_string()),
+    }
+}
+
+fn optional(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
+}
+
+fn read_item(conn: &Connection, item: &str) -> Option<TrayItem> {
+    let (service, path) = split_item(item);
+    let proxy = conn.with_proxy(service.as_str(), path.as_str(), TIMEOUT);
+
+    let text = |property| optional(proxy.get::<String>(ITEM, property).ok());
+    let pixmap = |property| {
+        proxy
+            .get::<Vec<(i32, i32, Vec<u8>)>>(ITEM, property)
+            .ok()
+            .and_then(Pixmap::largest)
+    };
+
+    // Every item has an Id, so its absence means the item is gone.
+    let name: String = proxy.get(ITEM, "Id").ok()?;
+
+    Some(TrayItem {
+        id: item.to_string(),
+        name,
+        title: text("Title").unwrap_or_default(),
+        status: TrayStatus::from_sni(&text("Status").unwrap_or_default()),
+        icon_name: text("IconName"),
+        icon_pixmap: pixmap("IconPixmap"),
+        attention_icon_name: text("AttentionIconName"),
+        attention_icon_pixmap: pixmap("AttentionIconPixmap"),
+        icon_theme_path: text("IconThemePath"),
+        tooltip: proxy
+            .get::<(String, Vec<(i32, i32, Vec<u8>)>, String, String)>(ITEM, "ToolTip")
+            .ok()
+            .map(|(_, _, title, description)| (title, description))
+            .filter(|(title, description)| !title.is_empty() || !description.is_empty()),
+        item_is_menu: proxy.get(ITEM, "ItemIsMenu").unwrap_or(false),
+        menu: proxy
+            .get::<Path<'static>>(ITEM, "Menu")
+            .ok()
+            .filter(|menu| &**menu != "/"),
+        service,
+        path,
+    })
+}
+
+/// Items registered with the watcher, shared between the watcher's method handlers and the
+/// host loop.
+type Registry = Arc<Mutex<Vec<String>>>;
+
+fn emit(conn: &Connection, member: &str, service: &str) {
+    if let Ok(signal) = Message::new_signal(WATCHER_PATH, WATCHER, member) {
+        let _ = conn.send(signal.append1(service));
+    }
+}
+
+/// Answers a method call to our StatusNotifierWatcher.
+fn handle_watcher(message: &Message, conn: &Connection, registry: &Registry) -> Option<Message> {
+    let items = || {
+        registry
+            .lock()
+            .map(|items| items.clone())
+            .unwrap_or_default()
+    };
+    let property = |name: &str| -> Option<Variant<Box<dyn RefArg>>> {
+        match name {
+            "RegisteredStatusNotifierItems" => Some(Variant(Box::new(items()))),
+            "IsStatusNotifierHostRegistered" => Some(Variant(Box::new(true))),
+            "ProtocolVersion" => Some(Variant(Box::new(0i32))),
+            _ => None,
+        }
+    };
+
+    let reply = match (message.interface().as_deref(), message.member().as_deref()) {
+        (Some(WATCHER), Some("RegisterStatusNotifierItem")) => {
+            let argument: &str = message.read1().ok()?;
+            let sender = message.sender()?;
+            let sender: &str = &sender;
+
+            // Items register either a bus name, or just a path on their own connection.
+            let item = if argument.starts_with('/') {
+                format!("{}{}", sender, argument)
+            } else {
+                format!("{}{}", argument, ITEM_PATH)
+            };
+
+            if let Ok(mut items) = registry.lock() {
+                if !items.contains(&item) {
+                    items.push(item.clone());
+                    emit(conn, "StatusNotifierItemRegistered", &item);
+                }
+            }
+
+            message.method_return()
+        }
+        // We are the only host we know of, and are always registered.
+        (Some(WATCHER), Some("RegisterStatusNotifierHost")) => message.method_return(),
+        (Some(PROPERTIES), Some("Get")) => {
+            let (_, name): (&str, &str) = message.read2().ok()?;
+
+            match property(name) {
+                Some(value) => message.method_return().append1(value),
+                None => message.error(
+                    &"org.freedesktop.DBus.Error.UnknownProperty".into(),
+                    &CString::new(format!("No such property: {}", name)).ok()?,
+                ),
+            }
+        }
+        (Some(PROPERTIES), Some("GetAll")) => {
+            let properties: HashMap<String, Variant<Box<dyn RefArg>>> = [
+                "RegisteredStatusNotifierItems",
+                "IsStatusNotifierHostRegistered",
+                "ProtocolVersion",
+            ]
+            .into_iter()
+            .filter_map(|name| Some((name.to_string(), property(name)?)))
+            .collect();
+
+            message.method_return().append1(properties)
+        }
+        _ => return None,
+    };
+
+    Some(reply)
+}
+
+/// Becomes the StatusNotifierWatcher if nobody else is, keeping track of items in the returned
+/// registry. Returns `None` if another watcher, e.g. the desktop's own, is already running.
+fn become_watcher(conn: &Connection, changed: &Arc<AtomicBool>) -> Option<Registry> {
+    let reply = conn.request_name(WATCHER, false, false, true).ok()?;
+
+    if reply != RequestNameReply::PrimaryOwner {
+        return None;
+    }
+
+    let registry: Registry = Arc::new(Mutex::new(Vec::new()));
+
+    let (items, flag) = (Arc::clone(&registry), Arc::clone(changed));
+    conn.start_receive(
+        MatchRule::new()
+            .with_type(MessageType::MethodCall)
+            .with_path(WATCHER_PATH),
+        Box::new(move |message, conn| {
+            let reply = handle_watcher(&message, conn, &items).unwrap_or_else(|| {
+                message.error(
+                    &"org.freedesktop.DBus.Error.UnknownMethod".into(),
+                    &CString::new("Unknown method").unwrap_or_default(),
+                )
+            });
+
+            let _ = conn.send(reply);
+            flag.store(true, Ordering::Release);
+            true
+        }),
+    );
+
+    // Items disappear along with their connection, without unregistering.
+    let (items, flag) = (Arc::clone(&registry), Arc::clone(changed));
+    let rule = MatchRule::new_signal(DBUS, "NameOwnerChanged").with_sender(DBUS);
+    let _ = conn.add_match_no_cb(&rule.match_str());
+    conn.start_receive(
+        rule,
+        Box::new(move |message, conn| {
+            if let (Some(name), _, Some("")) = message.get3::<&str, &str, &str>() {
+                if let Ok(mut items) = items.lock() {
+                    let gone: Vec<String> = items
+                        .iter()
+                        .filter(|item| split_item(item).0 == name)
+                        .cloned()
+                        .collect();
+
+                    items.retain(|item| !gone.contains(item));
+
+                    for item in gone {
+                        emit(conn, "StatusNotifierItemUnregistered", &item);
+                        flag.store(true, Ordering::Release);
+                    }
+                }
+            }
+            true
+        }),
+    );
+
+    Some(registry)
+}
+
+enum Request {
+    Activate(String, String, i32, i32),
+    SecondaryActivate(String, String, i32, i32),
+    ContextMenu(String, String, i32, i32),
+    Scroll(String, String, i32, bool),
+    MenuClicked(String, Path<'static>, i32),
+}
+
+fn handle(conn: &Connection, request: Request) -> Result<(), dbus::Error> {
+    match request {
+        Request::Activate(service, path, x, y) => conn
+            .with_proxy(service, path, TIMEOUT)
+            .method_call(ITEM, "Activate", (x, y)),
+        Request::SecondaryActivate(service, path, x, y) => conn
+            .with_proxy(service, path, TIMEOUT)
+            .method_call(ITEM, "SecondaryActivate", (x, y)),
+        Request::ContextMenu(service, path, x, y) => conn
+            .with_proxy(service, path, TIMEOUT)
+            .method_call(ITEM, "ContextMenu", (x, y)),
+        Request::Scroll(service, path, delta, horizontal) => {
+            let orientation = if horizontal { "horizontal" } else { "vertical" };
+
+            conn.with_proxy(service, path, TIMEOUT).method_call(
+                ITEM,
+                "Scroll",
+                (delta, orientation),
+            )
+        }
+        Request::MenuClicked(service, menu, id) => conn
+            .with_proxy(service, menu, TIMEOUT)
+            .method_call(DBUSMENU, "Event", (id, "clicked", Variant(0i32), 0u32)),
+    }
+}
+
+/// The tray's host: it acts as the StatusNotifierWatcher if nobody else does, or registers as
+/// a host with the existing one, then reads every item whenever one is added, removed or
+/// changed.
+struct Host {
+    /// The items registered with this host, if it is the watcher.
+    registry: Option<Registry>,
+}
+
+impl Service for Host {
+    type State = Vec<TrayItem>;
+    type Request = Request;
+
+    fn connect(conn: &Connection, changed: &Arc<AtomicBool>) -> Option<Self> {
+        let registry = become_watcher(conn, changed);
+
+        let host = format!("org.kde.StatusNotifierHost-{}", process::id());
+        let _ = conn.request_name(host.as_str(), false, false, true);
+
+        if registry.is_none() {
+            let _: Result<(), dbus::Error> = conn
+                .with_proxy(WATCHER, WATCHER_PATH, TIMEOUT)
+                .method_call(WATCHER, "RegisterStatusNotifierHost", (&host,));
+        }
+
+        Some(Host { registry })
+    }
+
+    fn rules(&self) -> Vec<MatchRule<'static>> {
+        vec![
+            MatchRule::new_signal(WATCHER, "StatusNotifierItemRegistered"),
+            MatchRule::new_signal(WATCHER, "StatusNotifierItemUnregistered"),
+            MatchRule::new()
+                .with_type(MessageType::Signal)
+                .with_interface(ITEM),
+        ]
+    }
+
+    fn read(&mut self, conn: &Connection, _: &Vec<TrayItem>) -> Vec<TrayItem> {
+        let registered = match &self.registry {
+            Some(registry) => registry
+                .lock()
+                .map(|items| items.clone())
+                .unwrap_or_default(),
+            None => conn
+                .with_proxy(WATCHER, WATCHER_PATH, TIMEOUT)
+                .get::<Vec<String>>(WATCHER, "RegisteredStatusNotifierItems")
+                .unwrap_or_default(),
+        };
+
+        registered
+            .iter()
+            .filter_map(|item| read_item(conn, item))
+            .collect()
+    }
+
+    fn handle(&mut self, conn: &Connection, _: &Vec<TrayItem>, request: Request) {
+        let _ = handle(conn, request);
+    }
+}
+
+fn read_bool(arg: &dyn RefArg) -> Option<bool> {
+    arg.as_i64().map(|value| value != 0)
+}
+
+/// A `(ia{sv}av)` dbusmenu layout node: its id, its properties, and its children, each a
+/// variant holding another node.
+type Layout = (i32, PropMap, Vec<Variant<Box<dyn RefArg>>>);
+
+/// Reads a child layout node, which is only decoded as a generic value.
+fn read_layout(node: &dyn RefArg) -> Option<Layout> {
+    let mut fields = node.as_iter()?;
+
+    let id = fields.next()?.as_i64()? as i32;
+
+    let mut entries = fields.next()?.as_iter()?;
+    let mut properties = PropMap::new();
+
+    // Values are variants, so they are unwrapped to match the ones of a decoded `PropMap`.
+    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+        let value = value.as_iter()?.next()?.box_clone();
+        properties.insert(key.as_str()?.to_string(), Variant(value));
+    }
+
+    let children = fields
+        .next()?
+        .as_iter()?
+        .filter_map(|child| Some(Variant(child.as_iter()?.next()?.box_clone())))
+        .collect();
+
+    Some((id, properties, children))
+}
+
+fn parse_menu((id, properties, children): Layout) -> MenuItem {
+    let mut item = MenuItem {
+        id,
+        enabled: true,
+        visible: true,
+        ..Default::default()
+    };
+
+    let mut toggle_type = None;
+
+    for (key, value) in &properties {
+        match key.as_str() {
+            "label" => item.label = value.as_str().unwrap_or_default().to_string(),
+            "enabled" => item.enabled = read_bool(value).unwrap_or(true),
+            "visible" => item.visible = read_bool(value).unwrap_or(true),
+            "type" => item.separator = value.as_str() == Some("separator"),
+            "toggle-type" => toggle_type = value.as_str().map(str::to_string),
+            "toggle-state" => item.toggled = value.as_i64().map(|state| state == 1),
+            _ => (),
+        }
+    }
+
+    if toggle_type.is_none() {
+        item.toggled = None;
+    } else if item.toggled.is_none() {
+        item.toggled = Some(false);
+    }
+
+    item.children = children
+        .iter()
+        .filter_map(|child| read_layout(&*child.0))
+        .map(parse_menu)
+        .collect();
+
+    item
+}
+
+fn read_menu(bus: &Bus, service: &str, menu: &Path) -> Result<Vec<MenuItem>, dbus::Error> {
+    let conn = bus.connect()?;
+    let proxy = conn.with_proxy(service, menu, TIMEOUT);
+
+    // Lets the application update the menu before it is shown; whether it did doesn't matter.
+    let _: Result<(bool,), dbus::Error> = proxy.method_call(DBUSMENU, "AboutToShow", (0i32,));
+
+    let (_, layout): (u32, Layout) =
+        proxy.method_call(DBUSMENU, "GetLayout", (0i32, -1i32, Vec::<String>::new()))?;
+
+    Ok(parse_menu(layout).children)
+}
+
+/// The system tray, following StatusNotifierItems such as Nextcloud, Discord or nm-applet.
+///
+/// If no StatusNotifierWatcher is running, the tray becomes one, so items can register with
+/// it. Must be used from the GTK main thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tray {
+    bus: Bus,
+}
+
+impl Default for Tray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tray {
+    /// Follows the tray on the session bus.
+    pub fn new() -> Self {
+        Tray::on(Bus::Session)
+    }
+
+    /// Follows the tray on the given bus, e.g. a private bus with mock items.
+    pub fn on(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    /// Returns a `Variable` with every item, in the order they registered. It updates as soon
+    /// as an item is added, removed or changes.
+    pub fn watch(&self) -> Variable<Vec<TrayItem>> {
+        Backend::<Host>::on(&self.bus).state()
+    }
+
+    /// Activates an item, usually on left click, at the given screen position.
+    pub fn activate(&self, item: &TrayItem, x: i32, y: i32) {
+        Backend::<Host>::on(&self.bus).send(Request::Activate(
+            item.service.clone(),
+            item.path.clone(),
+            x,
+            y,
+        ));
+    }
+
+    /// Secondary activates an item, usually on middle click.
+    pub fn secondary_activate(&self, item: &TrayItem, x: i32, y: i32) {
+        Backend::<Host>::on(&self.bus).send(Request::SecondaryActivate(
+            item.service.clone(),
+            item.path.clone(),
+            x,
+            y,
+        ));
+    }
+
+    /// Asks an item without a dbusmenu to show its own context menu.
+    pub fn context_menu(&self, item: &TrayItem, x: i32, y: i32) {
+        Backend::<Host>::on(&self.bus).send(Request::ContextMenu(
+            item.service.clone(),
+            item.path.clone(),
+            x,
+            y,
+        ));
+    }
+
+    /// Scrolls over an item, e.g. to change the volume of a player.
+    pub fn scroll(&self, item: &TrayItem, delta: i32, horizontal: bool) {
+        Backend::<Host>::on(&self.bus).send(Request::Scroll(
+            item.service.clone(),
+            item.path.clone(),
+            delta,
+            horizontal,
+        ));
+    }
+
+    /// Reads an item's context menu on a worker thread, then hands it to `callback` on the main
+    /// thread. Nothing is called if the item has no menu or it can't be read.
+    pub fn menu(&self, item: &TrayItem, callback: impl FnOnce(Vec<MenuItem>) + 'static) {
+        let Some(menu) = item.menu.clone() else {
+            return;
+        };
+
+        let (bus, service) = (self.bus.clone(), item.service.clone());
+
+        MainContext::default().spawn_local(async move {
+            let handle = gio::spawn_blocking(move || read_menu(&bus, &service, &menu));
+
+            if let Ok(Ok(entries)) = handle.await {
+                callback(entries);
+            }
+        });
+    }
+
+    /// Clicks an entry of an item's context menu.
+    pub fn menu_clicked(&self, item: &TrayItem, id: i32) {
+        if let Some(menu) = &item.menu {
+            Backend::<Host>::on(&self.bus).send(Request::MenuClicked(
+                item.service.clone(),
+                menu.clone(),
+                id,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbus::arg::RefArg;
+
+    use super::*;
+    use crate::sources::fixture::{props, variant, MockService, TestBus};
+
+    const MENU_PATH: &str = "/MenuBar";
+
+    #[test]
+    fn converts_the_largest_pixmap_to_rgba() {
+        let small = (1, 1, vec![0xff, 0x10, 0x20, 0x30]);
+        let large = (2, 1, vec![0x80, 0x01, 0x02, 0x03, 0x00, 0xaa, 0xbb, 0xcc]);
+
+        assert_eq!(
+            Pixmap::largest(vec![small, large]),
+            Some(Pixmap {
+                width: 2,
+                height: 1,
+                rgba: vec![0x01, 0x02, 0x03, 0x80, 0xaa, 0xbb, 0xcc, 0x00],
+            })
+        );
+    }
+
+    #[test]
+    fn skips_pixmaps_not_matching_their_size() {
+        assert_eq!(Pixmap::largest(vec![(2, 2, vec![0; 4])]), None);
+        assert_eq!(Pixmap::largest(vec![(0, 0, Vec::new())]), None);
+        assert_eq!(Pixmap::largest(vec![(-1, -1, vec![0; 4])]), None);
+        assert_eq!(
+            Pixmap::largest(vec![(i32::MAX, i32::MAX, vec![0; 4])]),
+            None
+        );
+        assert_eq!(Pixmap::largest(Vec::new()), None);
+    }
+
+    fn node(id: i32, properties: PropMap, children: Vec<Layout>) -> Layout {
+        let children = children
+            .into_iter()
+            .map(|child| Variant(Box::new(child) as Box<dyn RefArg>))
+            .collect();
+
+        (id, properties, children)
+    }
+
+    /// A menu with a plain entry, a separator, a checkbox and a disabled submenu.
+    fn layout() -> Layout {
+        node(
+            0,
+            PropMap::new(),
+            vec![
+                node(
+                    1,
+                    props([("label", variant("_Open".to_string()))]),
+                    Vec::new(),
+                ),
+                node(
+                    2,
+                    props([("type", variant("separator".to_string()))]),
+                    Vec::new(),
+                ),
+                node(
+                    3,
+                    props([
+                        ("label", variant("Sync".to_string())),
+                        ("toggle-type", variant("checkmark".to_string())),
+                        ("toggle-state", variant(1i32)),
+                    ]),
+                    Vec::new(),
+                ),
+                node(
+                    4,
+                    props([
+                        ("label", variant("More".to_string())),
+                        ("enabled", variant(false)),
+                        ("children-display", variant("submenu".to_string())),
+                    ]),
+                    vec![node(
+                        5,
+                        props([
+                            ("label", variant("About".to_string())),
+                            ("visible", variant(false)),
+                            ("toggle-type", variant("radio".to_string())),
+                        ]),
+                        Vec::new(),
+                    )],
+                ),
+            ],
+        )
+    }
+
+    fn entry(id: i32, label: &str) -> MenuItem {
+        MenuItem {
+            id,
+            label: label.to_string(),
+            enabled: true,
+            visible: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn decodes_nested_menu_layouts() {
+        let test_bus = TestBus::start();
+        let service = "org.example.App";
+        let _app = MockService::start(&test_bus, service, Vec::new, |message| {
+            match message.member().as_deref()? {
+                "AboutToShow" => Some(message.method_return().append1(false)),
+                "GetLayout" => Some(message.method_return().append2(7u32, layout())),
+                _ => None,
+            }
+        });
+
+        let menu = read_menu(&test_bus.bus(), service, &Path::from(MENU_PATH)).unwrap();
+
+        assert_eq!(
+            menu,
+            [
+                entry(1, "_Open"),
+                MenuItem {
+                    separator: true,
+                    ..entry(2, "")
+                },
+                MenuItem {
+                    toggled: Some(true),
+                    ..entry(3, "Sync")
+                },
+                MenuItem {
+                    enabled: false,
+                    children: vec![MenuItem {
+                        visible: false,
+                        toggled: Some(false),
+                        ..entry(5, "About")
+                    }],
+                    ..entry(4, "More")
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use gtk4::{
+    glib::markup_escape_text,
+    prelude::{ButtonExt, CastNone},
+    Button, Label,
+};
+
+/// Text for a Tag, explicitly marked as either plain text or Pango markup.
+///
+/// Plain text is never parsed, so untrusted strings (window titles, media metadata, etc.) can
+/// be displayed safely. Markup should be built with `Span`, which escapes its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Text {
+    Plain(String),
+    Markup(String),
+}
+
+impl Text {
+    /// Creates plain text, which is displayed as-is.
+    pub fn plain(text: &str) -> Self {
+        Text::Plain(text.to_string())
+    }
+
+    /// Creates text from trusted Pango markup. The markup is not escaped.
+    pub fn markup(markup: &str) -> Self {
+        Text::Markup(markup.to_string())
+    }
+
+    /// Returns the text as Pango markup, escaping it if it is plain.
+    pub fn to_markup(&self) -> String {
+        match self {
+            Text::Plain(text) => markup_escape_text(text).to_string(),
+            Text::Markup(markup) => markup.clone(),
+        }
+    }
+
+    /// Joins several pieces of text together. The result is only markup if one of the pieces is,
+    /// in which case the plain pieces are escaped.
+    pub fn join(parts: impl IntoIterator<Item = Text>) -> Self {
+        let parts: Vec<Text> = parts.into_iter().collect();
+
+        if parts.iter().any(|part| matches!(part, Text::Markup(_))) {
+            Text::Markup(parts.iter().map(Text::to_markup).collect())
+        } else {
+            Text::Plain(
+                parts
+                    .into_iter()
+                    .map(|part| match part {
+                        Text::Plain(text) | Text::Markup(text) => text,
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    /// Sets the text of a GTK4 `Label`.
+    pub(crate) fn apply_label(&self, label: &Label) {
+        match self {
+            Text::Plain(text) => label.set_text(text),
+            Text::Markup(markup) => label.set_markup(markup),
+        }
+    }
+
+    /// Sets the label of a GTK4 `Button`. Markup is set on the button's `Label` child, which
+    /// replaces any other child the button had.
+    pub(crate) fn apply_button(&self, button: &Button) {
+        match self {
+            Text::Plain(text) => {
+                button.set_label(text);
+            }
+            Text::Markup(markup) => match button.child().and_downcast::<Label>() {
+                Some(label) => label.set_markup(markup),
+                None => {
+                    let label = Label::new(None);
+                    label.set_markup(markup);
+
+                    button.set_child(Some(&label));
+                }
+            },
+        }
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Text::Plain(text.to_string())
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Text::Plain(text)
+    }
+}
+
+impl From<&String> for Text {
+    fn from(text: &String) -> Self {
+        Text::Plain(text.clone())
+    }
+}
+
+/// A typed Pango `<span>`, used to build markup without writing it by hand.
+/// Both the content and attribute values are escaped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    content: Vec<Text>,
+    foreground: Option<String>,
+    background: Option<String>,
+    weight: Option<String>,
+    size: Option<String>,
+    font: Option<String>,
+}
+
+impl Span {
+    /// Creates a new `Span` containing the given text.
+    pub fn new(content: impl Into<Text>) -> Self {
+        Self {
+            content: vec![content.into()],
+            ..Default::default()
+        }
+    }
+
+    /// Appends more text (or a nested `Span`) to the content.
+    pub fn push(mut self, content: impl Into<Text>) -> Self {
+        self.content.push(content.into());
+        self
+    }
+
+    /// Sets the foreground color, e.g. `#FFFFFF` or `red`.
+    pub fn foreground(mut self, color: &str) -> Self {
+        self.foreground = Some(color.to_string());
+        self
+    }
+
+    /// Sets the background color, e.g. `#000000` or `black`.
+    pub fn background(mut self, color: &str) -> Self {
+        self.background = Some(color.to_string());
+        self
+    }
+
+    /// Sets the font weight, e.g. `bold` or `600`.
+    pub fn weight(mut self, weight: &str) -> Self {
+        self.weight = Some(weight.to_string());
+        self
+    }
+
+    /// Sets the font size, e.g. `large` or `14pt`.
+    pub fn size(mut self, size: &str) -> Self {
+        self.size = Some(size.to_string());
+        self
+    }
+
+    /// Sets the font description, e.g. `JetBrains Mono 12`.
+    pub fn font(mut self, font: &str) -> Self {
+        self.font = Some(font.to_string());
+        self
+    }
+
+    /// Renders the `Span` as Pango markup.
+    pub fn to_markup(&self) -> String {
+        let attributes = [
+            ("foreground", &self.foreground),
+            ("background", &self.background),
+            ("weight", &self.weight),
+            ("size", &self.size),
+            ("font", &self.font),
+        ];
+
+        let mut markup = String::from("<span");
+
+        for (name, value) in attributes {
+            if let Some(value) = value {
+                markup.push_str(&format!(" {}='{}'", name, markup_escape_text(value)));
+            }
+        }
+
+        markup.push('>');
+
+        for content in &self.content {
+            markup.push_str(&content.to_markup());
+        }
+
+        markup.push_str("</span>");
+        markup
+    }
+}
+
+impl From<Span> for Text {
+    fn from(span: Span) -> Self {
+        Text::Markup(span.to_markup())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_plain_text_only() {
+        assert_eq!(
+            Text::plain("<b>&</b>").to_markup(),
+            "&lt;b&gt;&amp;&lt;/b&gt;"
+        );
+        assert_eq!(Text::markup("<b>bold</b>").to_markup(), "<b>bold</b>");
+        assert_eq!(Text::from("a & b"), Text::Plain("a & b".to_string()));
+    }
+
+    #[test]
+    fn joins_into_markup_only_if_a_piece_is_markup() {
+        assert_eq!(
+            Text::join([Text::plain("a < "), Text::plain("b")]),
+            Text::Plain("a < b".to_string())
+        );
+        assert_eq!(
+            Text::join([Text::plain("a < "), Text::markup("<b>b</b>")]),
+            Text::Markup("a &lt; <b>b</b>".to_string())
+        );
+    }
+
+    #[test]
+    fn escapes_span_content_and_attributes() {
+        let span = Span::new("Tom & Jerry <3")
+            .foreground("#fff' weight='bold")
+            .size("large");
+
+        assert_eq!(
+            span.to_markup(),
+            "<span foreground='#fff&apos; weight=&apos;bold' size='large'>\
+             Tom &amp; Jerry &lt;3</span>"
+        );
+    }
+
+    #[test]
+    fn nests_spans_without_escaping_them_twice() {
+        let text = Text::from(
+            Span::new("CPU ")
+                .weight("bold")
+                .push(Span::new("5 < 10").foreground("red")),
+        );
+
+        assert_eq!(
+            text,
+            Text::Markup(
+                "<span weight='bold'>CPU <span foreground='red'>5 &lt; 10</span></span>"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,98 @@
+/// Which side of a `Threshold` value activates its CSS class.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Above,
+    Below,
+}
+
+/// A CSS class that is added to a Tag while its bound value crosses a limit, and removed
+/// again once it no longer does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    comparison: Comparison,
+    value: f64,
+    class: String,
+}
+
+impl Threshold {
+    /// Activates `class` while the value is greater than or equal to `value`.
+    pub fn above(value: f64, class: &str) -> Self {
+        Self {
+            comparison: Comparison::Above,
+            value,
+            class: class.to_string(),
+        }
+    }
+
+    /// Activates `class` while the value is less than or equal to `value`.
+    pub fn below(value: f64, class: &str) -> Self {
+        Self {
+            comparison: Comparison::Below,
+            value,
+            class: class.to_string(),
+        }
+    }
+
+    /// Returns true if the given value activates this threshold. `NaN` never does.
+    pub fn matches(&self, value: f64) -> bool {
+        match self.comparison {
+            Comparison::Above => value >= self.value,
+            Comparison::Below => value <= self.value,
+        }
+    }
+
+    /// Returns true once the value has moved back past the limit by more than `hysteresis`,
+    /// e.g. below 85 for `Threshold::above(90.0, ..)` with a hysteresis of 5.
+    pub(crate) fn clears(&self, value: f64, hysteresis: f64) -> bool {
+        match self.comparison {
+            Comparison::Above => value < self.value - hysteresis,
+            Comparison::Below => value > self.value + hysteresis,
+        }
+    }
+
+    /// The CSS class toggled by this threshold.
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_at_and_beyond_the_limit() {
+        let high = Threshold::above(90.0, "high");
+        let low = Threshold::below(15.0, "low");
+
+        assert!(high.matches(90.0));
+        assert!(high.matches(99.5));
+        assert!(!high.matches(89.9));
+
+        assert!(low.matches(15.0));
+        assert!(low.matches(3.0));
+        assert!(!low.matches(15.1));
+
+        assert_eq!(high.class(), "high");
+    }
+
+    #[test]
+    fn never_matches_nan() {
+        assert!(!Threshold::above(90.0, "high").matches(f64::NAN));
+        assert!(!Threshold::below(15.0, "low").matches(f64::NAN));
+    }
+
+    #[test]
+    fn clears_only_past_the_hysteresis() {
+        let high = Threshold::above(90.0, "high");
+        let low = Threshold::below(15.0, "low");
+
+        assert!(!high.clears(87.0, 5.0));
+        assert!(!high.clears(85.0, 5.0));
+        assert!(high.clears(84.9, 5.0));
+        assert!(high.clears(89.9, 0.0));
+
+        assert!(!low.clears(19.0, 5.0));
+        assert!(low.clears(20.1, 5.0));
+    }
+}
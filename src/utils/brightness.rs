@@ -0,0 +1,48 @@
+use gtk4::Application;
+use gtk4_layer_shell::Edge;
+
+use crate::{
+    sources::{Backlight, Brightness},
+    utils::tag_label,
+    widgets::{Slab, Tag},
+    Builder, Internal,
+};
+
+fn format_brightness(brightness: &Option<Brightness>) -> String {
+    match brightness {
+        Some(brightness) => format!("{:.0}%", brightness.percent()),
+        None => String::new(),
+    }
+}
+
+/// Creates a label with a specified CSS class name, showing the brightness of a backlight.
+/// Scrolling over it raises or lowers the brightness by `step` percentage points.
+pub fn tag_brightness(class_name: &str, backlight: Backlight, step: i32) -> Tag {
+    let tag = tag_label(class_name);
+
+    backlight.watch().bind(&tag, format_brightness);
+
+    Internal::static_scroll(&tag, move |dy| {
+        backlight.step(if dy < 0.0 { step } else { -step });
+    });
+
+    tag
+}
+
+/// Builds a brightness OSD: a Slab that shows the brightness of a backlight for `duration`
+/// seconds whenever it changes, e.g. through the brightness keys.
+pub fn brightness_osd(
+    factory: Application,
+    class_name: &str,
+    backlight: Backlight,
+    margins: Vec<(Edge, i32)>,
+    anchors: Vec<(Edge, bool)>,
+    duration: u64,
+) {
+    let tag = tag_label(class_name);
+
+    // Bound before the Slab is built, so the initial value doesn't show it.
+    backlight.watch().bind(&tag, format_brightness);
+
+    Slab::new(factory, "Brightness", tag, margins, anchors, duration).build();
+}
@@ -0,0 +1,216 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gio::File;
+use gtk4::{
+    gdk::{Paintable, Texture},
+    glib::{self, MainContext},
+    pango::EllipsizeMode,
+    prelude::{BoxExt, ButtonExt, Cast, WidgetExt},
+    Align, Box, Button, Label, Orientation, Picture,
+};
+
+use crate::{
+    scheduler::{schedule_mapped, Interval},
+    sources::{Media, PlaybackStatus, Player},
+    widgets::Tag,
+};
+
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn format_position(player: &Player) -> String {
+    let position = player.current_position();
+
+    match player.length {
+        Some(length) => format!("{} / {}", format_time(position), format_time(length)),
+        None => format_time(position),
+    }
+}
+
+/// Downloads remote album art on a worker thread.
+fn download(url: String) -> Option<glib::Bytes> {
+    let response = reqwest::blocking::get(url).ok()?.error_for_status().ok()?;
+
+    Some(glib::Bytes::from_owned(response.bytes().ok()?))
+}
+
+/// Shows the album art at a URL. Local art is loaded right away, while remote art is shown once
+/// downloaded, unless the art changed in the meantime.
+fn load_art(art: &Picture, url: Option<&str>, shown: &Rc<RefCell<Option<String>>>) {
+    match url {
+        Some(url) if url.starts_with("file://") => {
+            art.set_file(Some(&File::for_uri(url)));
+            art.set_visible(true);
+        }
+        Some(url) if url.starts_with("https://") || url.starts_with("http://") => {
+            art.set_paintable(None::<&Paintable>);
+            art.set_visible(false);
+
+            let (art, shown, url) = (art.clone(), Rc::clone(shown), url.to_string());
+            MainContext::default().spawn_local(async move {
+                let bytes = gio::spawn_blocking({
+                    let url = url.clone();
+                    move || download(url)
+                })
+                .await;
+
+                if shown.borrow().as_deref() != Some(url.as_str()) {
+                    return;
+                }
+
+                let texture = bytes
+                    .ok()
+                    .flatten()
+                    .and_then(|bytes| Texture::from_bytes(&bytes).ok());
+
+                art.set_paintable(texture.as_ref());
+                art.set_visible(texture.is_some());
+            });
+        }
+        _ => {
+            art.set_paintable(None::<&Paintable>);
+            art.set_visible(false);
+        }
+    }
+}
+
+fn labelled(class: &str) -> Label {
+    let label = Label::new(None);
+    label.add_css_class(class);
+    label.set_xalign(0.0);
+    label.set_ellipsize(EllipsizeMode::End);
+    label
+}
+
+fn control(icon: &str, class: &str, action: impl Fn() + 'static) -> Button {
+    let button = Button::from_icon_name(icon);
+    button.add_css_class(class);
+    button.connect_clicked(move |_| action());
+    button
+}
+
+/// Creates a media player widget with a specified CSS class name, following the active MPRIS
+/// player: album art, title, artist, position, and previous, play/pause and next buttons.
+/// It is hidden while there is no player.
+///
+/// The box has a CSS class of "playing", "paused" or "stopped", and contains an "art",
+/// "title", "artist", "position", "previous", "play-pause" and "next" child. Remote album art
+/// is downloaded in the background, so it never blocks the UI.
+pub fn tag_media(class_name: &str) -> Tag {
+    let tag = Box::new(Orientation::Horizontal, 8);
+
+    tag.set_widget_name(class_name);
+
+    let media = Media::new();
+
+    let art = Picture::new();
+    art.add_css_class("art");
+    art.set_can_shrink(true);
+    art.set_visible(false);
+
+    let info = Box::new(Orientation::Vertical, 2);
+    info.set_valign(Align::Center);
+    info.set_hexpand(true);
+
+    let (title, artist, position) = (labelled("title"), labelled("artist"), labelled("position"));
+    info.append(&title);
+    info.append(&artist);
+    info.append(&position);
+
+    let controls = Box::new(Orientation::Horizontal, 4);
+    controls.set_valign(Align::Center);
+
+    let (previous, next) = (media.clone(), media.clone());
+    let previous = control("media-skip-backward-symbolic", "previous", move || {
+        previous.previous()
+    });
+    let next = control("media-skip-forward-symbolic", "next", move || next.next());
+
+    let toggle = media.clone();
+    let play_pause = control("media-playback-start-symbolic", "play-pause", move || {
+        toggle.play_pause()
+    });
+
+    controls.append(&previous);
+    controls.append(&play_pause);
+    controls.append(&next);
+
+    tag.append(&art);
+    tag.append(&info);
+    tag.append(&controls);
+
+    let current: Rc<RefCell<Option<Player>>> = Rc::new(RefCell::new(None));
+
+    // Players don't report their position while playing, so it is counted here.
+    let (ticking, ticker) = (Rc::clone(&current), position.clone());
+    schedule_mapped(tag.upcast_ref(), Interval::SECOND, move || {
+        if let Some(player) = ticking
+            .borrow()
+            .as_ref()
+            .filter(|player| player.status == PlaybackStatus::Playing)
+        {
+            ticker.set_text(&format_position(player));
+        }
+    });
+
+    let container = tag.clone();
+    let shown_art: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+
+    media.watch().subscribe(move |player: &Option<Player>| {
+        current.replace(player.clone());
+
+        let Some(player) = player else {
+            container.set_visible(false);
+            return;
+        };
+
+        container.set_visible(true);
+
+        title.set_text(&player.title);
+        artist.set_text(&player.artist);
+        artist.set_visible(!player.artist.is_empty());
+
+        position.set_text(&format_position(player));
+
+        // The art only changes with the track, so it isn't reloaded on every other change.
+        if *shown_art.borrow() != player.art_url {
+            shown_art.replace(player.art_url.clone());
+            load_art(&art, player.art_url.as_deref(), &shown_art);
+        }
+
+        previous.set_sensitive(player.can_go_previous);
+        next.set_sensitive(player.can_go_next);
+
+        for (status, class) in [
+            (PlaybackStatus::Playing, "playing"),
+            (PlaybackStatus::Paused, "paused"),
+            (PlaybackStatus::Stopped, "stopped"),
+        ] {
+            if player.status == status {
+                container.add_css_class(class);
+            } else {
+                container.remove_css_class(class);
+            }
+        }
+
+        play_pause.set_icon_name(if player.status == PlaybackStatus::Playing {
+            "media-playback-pause-symbolic"
+        } else {
+            "media-playback-start-symbolic"
+        });
+    });
+
+    Tag::Box(tag)
+}
@@ -0,0 +1,136 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use gtk4::{
+    prelude::{BoxExt, ButtonExt, RangeExt, ScaleExt, WidgetExt},
+    Box, Button, Image, Label, Orientation, Scale,
+};
+
+use crate::{
+    sources::{AppStream, Mixer},
+    widgets::Tag,
+};
+
+/// A single stream in the mixer: icon, name, volume slider and mute button.
+struct MixerRow {
+    row: Box,
+    icon: Image,
+    name: Label,
+    volume: Scale,
+    mute: Button,
+    // Set while the row is updated from PulseAudio, so the slider doesn't echo it back.
+    syncing: Rc<Cell<bool>>,
+}
+
+impl MixerRow {
+    fn new(index: u32) -> Self {
+        let row = Box::new(Orientation::Horizontal, 6);
+        row.add_css_class("stream");
+
+        let icon = Image::new();
+        icon.add_css_class("icon");
+
+        let name = Label::new(None);
+        name.add_css_class("name");
+
+        let volume = Scale::with_range(Orientation::Horizontal, 0.0, 100.0, 1.0);
+        volume.add_css_class("volume");
+        volume.set_draw_value(false);
+        volume.set_hexpand(true);
+
+        let mute = Button::new();
+        mute.add_css_class("mute");
+
+        let syncing = Rc::new(Cell::new(false));
+
+        let sync_flag = Rc::clone(&syncing);
+        volume.connect_value_changed(move |scale| {
+            if !sync_flag.get() {
+                Mixer::set_volume(index, scale.value().round() as u32);
+            }
+        });
+
+        mute.connect_clicked(move |_| Mixer::toggle_mute(index));
+
+        row.append(&icon);
+        row.append(&name);
+        row.append(&volume);
+        row.append(&mute);
+
+        Self {
+            row,
+            icon,
+            name,
+            volume,
+            mute,
+            syncing,
+        }
+    }
+
+    fn update(&self, stream: &AppStream) {
+        self.syncing.set(true);
+
+        match &stream.icon {
+            Some(icon) => {
+                self.icon.set_icon_name(Some(icon));
+                self.icon.set_visible(true);
+            }
+            None => self.icon.set_visible(false),
+        }
+
+        self.name.set_text(&stream.name);
+        self.volume.set_value(stream.volume as f64);
+
+        if stream.muted {
+            self.mute.set_icon_name("audio-volume-muted-symbolic");
+            self.row.add_css_class("muted");
+        } else {
+            self.mute.set_icon_name("audio-volume-high-symbolic");
+            self.row.remove_css_class("muted");
+        }
+
+        self.syncing.set(false);
+    }
+}
+
+/// Creates a volume mixer with a specified CSS class name, listing every application that is
+/// playing audio, with a volume slider and a mute button each. Rows are added and removed as
+/// streams appear and disappear.
+///
+/// Each row has a CSS class of "stream" (plus "muted" while muted), and contains an "icon",
+/// "name", "volume" and "mute" child.
+pub fn tag_mixer(class_name: &str) -> Tag {
+    let tag = Box::new(Orientation::Vertical, 4);
+
+    tag.set_widget_name(class_name);
+
+    let container = tag.clone();
+    let rows: RefCell<HashMap<u32, MixerRow>> = RefCell::new(HashMap::new());
+
+    Mixer::watch().subscribe(move |streams| {
+        let mut rows = rows.borrow_mut();
+
+        rows.retain(|index, row| {
+            let playing = streams.iter().any(|stream| stream.index == *index);
+            if !playing {
+                container.remove(&row.row);
+            }
+            playing
+        });
+
+        for stream in streams {
+            let row = rows.entry(stream.index).or_insert_with(|| {
+                let row = MixerRow::new(stream.index);
+                container.append(&row.row);
+                row
+            });
+
+            row.update(stream);
+        }
+    });
+
+    Tag::Box(tag)
+}
@@ -1,3 +1,18 @@
+mod brightness;
+mod media;
+mod mixer;
+mod notifications;
+mod switcher;
+mod tray;
 mod utils;
+mod wifi;
 
+pub use brightness::{brightness_osd, tag_brightness};
+pub use media::tag_media;
+pub use mixer::tag_mixer;
+pub(crate) use notifications::notification_card;
+pub use notifications::tag_notification_center;
+pub use switcher::tag_sink_switcher;
+pub use tray::tag_tray;
 pub use utils::*;
+pub use wifi::tag_wifi;
@@ -0,0 +1,258 @@
+use std::rc::Rc;
+
+use gio::glib::{Bytes, DateTime};
+use gtk4::{
+    gdk::{MemoryFormat, MemoryTexture},
+    pango::{self, WrapMode},
+    prelude::{BoxExt, ButtonExt, GestureSingleExt, WidgetExt},
+    Align, Box, Button, GestureClick, Image, Label, Orientation, PolicyType, ScrolledWindow,
+    Switch,
+};
+
+use crate::{
+    sources::{History, HistoryEntry, ImageData, Notification, NotificationImage},
+    widgets::Tag,
+    DoNotDisturb,
+};
+
+fn texture(image: &ImageData) -> MemoryTexture {
+    let format = if image.has_alpha {
+        MemoryFormat::R8g8b8a8
+    } else {
+        MemoryFormat::R8g8b8
+    };
+
+    MemoryTexture::new(
+        image.width,
+        image.height,
+        format,
+        &Bytes::from(&image.data),
+        image.rowstride as usize,
+    )
+}
+
+fn image(image: &NotificationImage) -> Image {
+    let widget = match image {
+        NotificationImage::Data(data) => Image::from_paintable(Some(&texture(data))),
+        NotificationImage::File(path) => Image::from_file(path),
+        NotificationImage::Icon(name) => Image::from_icon_name(name),
+    };
+
+    widget.add_css_class("image");
+    widget.set_pixel_size(48);
+    widget.set_valign(Align::Start);
+    widget
+}
+
+fn labelled(class: &str) -> Label {
+    let label = Label::new(None);
+    label.add_css_class(class);
+    label.set_xalign(0.0);
+    label.set_hexpand(true);
+    label.set_wrap(true);
+    label.set_wrap_mode(WrapMode::WordChar);
+    // Wraps to the width the card is given, instead of widening it.
+    label.set_max_width_chars(1);
+    label
+}
+
+/// Builds the card showing a notification: its image, app name, summary, body and action
+/// buttons, plus a close button and the time it arrived, if given. Clicking the card invokes
+/// its default action, or dismisses it if it has none.
+///
+/// The card has a CSS class of "notification" and one of "low", "normal" or "critical", and
+/// contains an "image", "app-name", "time", "summary", "body", "close" and "actions" child,
+/// with an "action" button per action.
+pub(crate) fn notification_card(
+    notification: &Notification,
+    time: Option<&str>,
+    on_action: impl Fn(&str) + 'static,
+    on_dismiss: impl Fn() + 'static,
+) -> Box {
+    let (on_action, on_dismiss) = (Rc::new(on_action), Rc::new(on_dismiss));
+
+    let card = Box::new(Orientation::Vertical, 4);
+    card.add_css_class("notification");
+    card.add_css_class(notification.urgency.class());
+
+    let header = Box::new(Orientation::Horizontal, 4);
+
+    let app_name = labelled("app-name");
+    app_name.set_text(&notification.app_name);
+
+    let close = Button::from_icon_name("window-close-symbolic");
+    close.add_css_class("close");
+    close.set_has_frame(false);
+
+    let dismiss = Rc::clone(&on_dismiss);
+    close.connect_clicked(move |_| dismiss());
+
+    header.append(&app_name);
+
+    if let Some(time) = time {
+        let label = Label::new(Some(time));
+        label.add_css_class("time");
+        header.append(&label);
+    }
+
+    header.append(&close);
+
+    let content = Box::new(Orientation::Horizontal, 8);
+
+    if let Some(picture) = &notification.image {
+        content.append(&image(picture));
+    }
+
+    let text = Box::new(Orientation::Vertical, 2);
+
+    let summary = labelled("summary");
+    summary.set_text(&notification.summary);
+
+    // Senders don't always escape their bodies, so invalid markup is shown as it is.
+    let body = labelled("body");
+    if pango::parse_markup(&notification.body, '\0').is_ok() {
+        body.set_markup(&notification.body);
+    } else {
+        body.set_text(&notification.body);
+    }
+    body.set_visible(!notification.body.is_empty());
+
+    text.append(&summary);
+    text.append(&body);
+    content.append(&text);
+
+    let actions = Box::new(Orientation::Horizontal, 4);
+    actions.add_css_class("actions");
+    actions.set_homogeneous(true);
+
+    for action in notification
+        .actions
+        .iter()
+        .filter(|action| action.key != "default")
+    {
+        let button = Button::with_label(&action.label);
+        button.add_css_class("action");
+
+        let (invoke, key) = (Rc::clone(&on_action), action.key.clone());
+        button.connect_clicked(move |_| invoke(&key));
+
+        actions.append(&button);
+    }
+    actions.set_visible(actions.first_child().is_some());
+
+    card.append(&header);
+    card.append(&content);
+    card.append(&actions);
+
+    let has_default = notification.default_action().is_some();
+
+    let click = GestureClick::new();
+    click.set_button(1);
+    click.connect_released(move |_, _, _, _| {
+        if has_default {
+            on_action("default");
+        } else {
+            on_dismiss();
+        }
+    });
+    card.add_controller(click);
+
+    card
+}
+
+/// Formats when an entry arrived as the time of day, adding the date if it wasn't today.
+fn format_received(received: i64) -> String {
+    let (Ok(time), Ok(today)) = (DateTime::from_unix_local(received), DateTime::now_local()) else {
+        return String::new();
+    };
+
+    let format = if time.ymd() == today.ymd() {
+        "%H:%M"
+    } else {
+        "%b %-d, %H:%M"
+    };
+
+    time.format(format).map(String::from).unwrap_or_default()
+}
+
+/// Creates a notification center with a specified CSS class name, listing the notifications
+/// kept in a history, newest first, in a scrollable list. Each entry can be dismissed, and a
+/// "Clear all" button empties the history. A switch turns Do Not Disturb on and off.
+///
+/// The header contains a "title" label, a "dnd" switch and a "clear-all" button, the entries
+/// are cards like the ones of a `Notifier` in an "entries" box, and an "empty" label shows
+/// while there are none.
+pub fn tag_notification_center(class_name: &str, history: History) -> Tag {
+    let tag = Box::new(Orientation::Vertical, 8);
+
+    tag.set_widget_name(class_name);
+
+    let header = Box::new(Orientation::Horizontal, 8);
+
+    let title = Label::new(Some("Notifications"));
+    title.add_css_class("title");
+    title.set_xalign(0.0);
+    title.set_hexpand(true);
+
+    let dnd = Switch::new();
+    dnd.add_css_class("dnd");
+    dnd.set_valign(Align::Center);
+    dnd.set_tooltip_text(Some("Do Not Disturb"));
+    dnd.connect_active_notify(|switch| DoNotDisturb::set(switch.is_active()));
+
+    let switch = dnd.clone();
+    DoNotDisturb::watch().subscribe(move |enabled| switch.set_active(*enabled));
+
+    let clear_all = Button::with_label("Clear all");
+    clear_all.add_css_class("clear-all");
+
+    let clearer = history.clone();
+    clear_all.connect_clicked(move |_| clearer.clear());
+
+    header.append(&title);
+    header.append(&dnd);
+    header.append(&clear_all);
+
+    let entries = Box::new(Orientation::Vertical, 8);
+    entries.add_css_class("entries");
+
+    let empty = Label::new(Some("No notifications"));
+    empty.add_css_class("empty");
+    empty.set_vexpand(true);
+
+    let scroller = ScrolledWindow::new();
+    scroller.set_policy(PolicyType::Never, PolicyType::Automatic);
+    scroller.set_vexpand(true);
+    scroller.set_child(Some(&entries));
+
+    tag.append(&header);
+    tag.append(&scroller);
+    tag.append(&empty);
+
+    let dismisser = history.clone();
+    history
+        .watch()
+        .subscribe(move |history: &Vec<HistoryEntry>| {
+            while let Some(child) = entries.first_child() {
+                entries.remove(&child);
+            }
+
+            for entry in history {
+                let (dismisser, key) = (dismisser.clone(), entry.key);
+
+                let card = notification_card(
+                    &entry.notification,
+                    Some(&format_received(entry.received)),
+                    |_| (),
+                    move || dismisser.dismiss(key),
+                );
+                entries.append(&card);
+            }
+
+            scroller.set_visible(!history.is_empty());
+            empty.set_visible(history.is_empty());
+            clear_all.set_sensitive(!history.is_empty());
+        });
+
+    Tag::Box(tag)
+}
@@ -0,0 +1,57 @@
+use gtk4::{
+    prelude::{BoxExt, ButtonExt, WidgetExt},
+    Box, Button, Orientation,
+};
+
+use crate::{sources::Sink, widgets::Tag};
+
+/// Creates an audio output switcher with a specified CSS class name, with a button for every
+/// available sink. Clicking a button makes that sink the default, and if `move_streams` is set,
+/// moves every playing stream over to it.
+///
+/// Each button has a CSS class of "sink", and the default sink's button also has "default".
+pub fn tag_sink_switcher(class_name: &str, move_streams: bool) -> Tag {
+    let tag = Box::new(Orientation::Vertical, 4);
+
+    tag.set_widget_name(class_name);
+
+    // Only names and descriptions matter here, so volume changes don't rebuild the buttons.
+    let sinks = Sink::list().map(|sinks| {
+        sinks
+            .iter()
+            .map(|sink| (sink.name.clone(), sink.description.clone()))
+            .collect::<Vec<_>>()
+    });
+    let default = Sink::watch().map(|sink| sink.as_ref().map(|sink| sink.name.clone()));
+
+    let container = tag.clone();
+
+    sinks
+        .combine(&default, |sinks, default| (sinks.clone(), default.clone()))
+        .subscribe(move |(sinks, default)| {
+            while let Some(child) = container.first_child() {
+                container.remove(&child);
+            }
+
+            for (name, description) in sinks {
+                let label = if description.is_empty() {
+                    name
+                } else {
+                    description
+                };
+                let button = Button::with_label(label);
+                button.add_css_class("sink");
+
+                if default.as_ref() == Some(name) {
+                    button.add_css_class("default");
+                }
+
+                let name = name.clone();
+                button.connect_clicked(move |_| Sink::set_default(&name, move_streams));
+
+                container.append(&button);
+            }
+        });
+
+    Tag::Box(tag)
+}
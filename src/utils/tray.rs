@@ -0,0 +1,253 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use gio::{
+    glib::{markup_escape_text, Bytes, Propagation},
+    prelude::{ActionMapExt, ToVariant},
+    Menu, SimpleAction, SimpleActionGroup,
+};
+use gtk4::{
+    gdk::{Display, MemoryFormat, MemoryTexture},
+    pango,
+    prelude::{BoxExt, EventControllerExt, GestureSingleExt, PopoverExt, WidgetExt},
+    Box, EventControllerScroll, EventControllerScrollFlags, GestureClick, IconTheme, Image,
+    Orientation, PopoverMenu, Widget,
+};
+
+use crate::{
+    sources::{MenuItem, Pixmap, Tray, TrayItem, TrayStatus},
+    widgets::Tag,
+};
+
+fn texture(pixmap: &Pixmap) -> MemoryTexture {
+    MemoryTexture::new(
+        pixmap.width,
+        pixmap.height,
+        MemoryFormat::R8g8b8a8,
+        &Bytes::from(&pixmap.rgba),
+        pixmap.width as usize * 4,
+    )
+}
+
+/// Shows an item's icon, preferring the attention icon while it asks for attention, and
+/// themed icons over pixmaps.
+fn update_icon(image: &Image, item: &TrayItem) {
+    if let Some(path) = &item.icon_theme_path {
+        if let Some(display) = Display::default() {
+            let theme = IconTheme::for_display(&display);
+
+            if !theme
+                .search_path()
+                .iter()
+                .any(|dir| dir.to_str() == Some(path))
+            {
+                theme.add_search_path(path);
+            }
+        }
+    }
+
+    let attention = item.status == TrayStatus::NeedsAttention;
+
+    let (name, pixmap) = if attention
+        && (item.attention_icon_name.is_some() || item.attention_icon_pixmap.is_some())
+    {
+        (&item.attention_icon_name, &item.attention_icon_pixmap)
+    } else {
+        (&item.icon_name, &item.icon_pixmap)
+    };
+
+    match (name, pixmap) {
+        (Some(name), _) => image.set_icon_name(Some(name)),
+        (None, Some(pixmap)) => image.set_paintable(Some(&texture(pixmap))),
+        (None, None) => image.set_icon_name(Some("image-missing")),
+    }
+
+    let tooltip = match &item.tooltip {
+        Some((title, description)) if description.is_empty() => {
+            markup_escape_text(title).to_string()
+        }
+        Some((title, description)) => {
+            // Descriptions may be markup, but aren't always escaped when they aren't.
+            let description = if pango::parse_markup(description, '\0').is_ok() {
+                description.to_string()
+            } else {
+                markup_escape_text(description).to_string()
+            };
+
+            format!("<b>{}</b>\n{}", markup_escape_text(title), description)
+        }
+        None => markup_escape_text(&item.title).to_string(),
+    };
+    image.set_tooltip_markup((!tooltip.is_empty()).then_some(tooltip.as_str()));
+
+    for (class, set) in [
+        ("attention", attention),
+        ("passive", item.status == TrayStatus::Passive),
+    ] {
+        if set {
+            image.add_css_class(class);
+        } else {
+            image.remove_css_class(class);
+        }
+    }
+}
+
+/// Builds a menu model from dbusmenu entries, registering an action for every clickable entry.
+/// Separators split the menu into sections.
+fn build_menu(
+    entries: &[MenuItem],
+    actions: &SimpleActionGroup,
+    on_click: &Rc<dyn Fn(i32)>,
+) -> Menu {
+    let menu = Menu::new();
+    let mut section = Menu::new();
+
+    for entry in entries.iter().filter(|entry| entry.visible) {
+        if entry.separator {
+            menu.append_section(None, &section);
+            section = Menu::new();
+            continue;
+        }
+
+        if !entry.children.is_empty() {
+            section.append_submenu(
+                Some(&entry.label),
+                &build_menu(&entry.children, actions, on_click),
+            );
+            continue;
+        }
+
+        let name = format!("item-{}", entry.id);
+        let action = match entry.toggled {
+            // A stateful action without a parameter is shown as a checkbox.
+            Some(toggled) => SimpleAction::new_stateful(&name, None, &toggled.to_variant()),
+            None => SimpleAction::new(&name, None),
+        };
+        action.set_enabled(entry.enabled);
+
+        let (on_click, id) = (Rc::clone(on_click), entry.id);
+        action.connect_activate(move |_, _| on_click(id));
+        actions.add_action(&action);
+
+        section.append(Some(&entry.label), Some(&format!("dbusmenu.{}", name)));
+    }
+
+    menu.append_section(None, &section);
+    menu
+}
+
+fn show_menu(parent: &Widget, tray: &Tray, item: &TrayItem, entries: Vec<MenuItem>) {
+    let actions = SimpleActionGroup::new();
+
+    let (clicker, clicked) = (tray.clone(), item.clone());
+    let on_click: Rc<dyn Fn(i32)> = Rc::new(move |id| clicker.menu_clicked(&clicked, id));
+
+    let popover = PopoverMenu::from_model(Some(&build_menu(&entries, &actions, &on_click)));
+    popover.insert_action_group("dbusmenu", Some(&actions));
+    popover.set_parent(parent);
+    popover.add_css_class("tray-menu");
+
+    popover.connect_closed(|popover| {
+        let popover = popover.clone();
+        // Unparenting right away would drop the popover before its action runs.
+        gio::glib::idle_add_local_once(move || popover.unparent());
+    });
+
+    popover.popup();
+}
+
+/// An item's icon, along with the item as last seen, which its click handlers read.
+type Icon = (Image, Rc<RefCell<TrayItem>>);
+
+fn tray_icon(tray: &Tray, item: &TrayItem) -> Icon {
+    let image = Image::new();
+    image.add_css_class("item");
+
+    let current = Rc::new(RefCell::new(item.clone()));
+
+    let click = GestureClick::new();
+    // Listens to every mouse button.
+    click.set_button(0);
+
+    let (clicker, clicked) = (tray.clone(), Rc::clone(&current));
+    click.connect_released(move |gesture, _, x, y| {
+        let item = clicked.borrow().clone();
+        let Some(widget) = gesture.widget() else {
+            return;
+        };
+
+        let (x, y) = (x as i32, y as i32);
+        let wants_menu =
+            gesture.current_button() == 3 || (item.item_is_menu && gesture.current_button() == 1);
+
+        match gesture.current_button() {
+            _ if wants_menu && item.has_menu() => {
+                let (tray, shown) = (clicker.clone(), item.clone());
+                clicker.menu(&item, move |entries| {
+                    show_menu(&widget, &tray, &shown, entries)
+                });
+            }
+            _ if wants_menu => clicker.context_menu(&item, x, y),
+            1 => clicker.activate(&item, x, y),
+            2 => clicker.secondary_activate(&item, x, y),
+            _ => (),
+        }
+    });
+    image.add_controller(click);
+
+    let scroll = EventControllerScroll::new(EventControllerScrollFlags::BOTH_AXES);
+    let (scroller, scrolled) = (tray.clone(), Rc::clone(&current));
+    scroll.connect_scroll(move |_, dx, dy| {
+        let item = scrolled.borrow();
+
+        if dy != 0.0 {
+            scroller.scroll(&item, dy.round() as i32, false);
+        } else if dx != 0.0 {
+            scroller.scroll(&item, dx.round() as i32, true);
+        }
+        Propagation::Stop
+    });
+    image.add_controller(scroll);
+
+    (image, current)
+}
+
+/// Creates a system tray with a specified CSS class name, showing an icon for every
+/// StatusNotifierItem. Left click activates an item, middle click secondary activates it,
+/// right click opens its context menu as a popover, and scrolling is passed on.
+///
+/// Each icon has a CSS class of "item", plus "attention" while it asks for attention and
+/// "passive" while it may be hidden. Menus have a CSS class of "tray-menu".
+pub fn tag_tray(class_name: &str) -> Tag {
+    let tag = Box::new(Orientation::Horizontal, 4);
+
+    tag.set_widget_name(class_name);
+
+    let tray = Tray::new();
+    let container = tag.clone();
+    let icons: RefCell<HashMap<String, Icon>> = RefCell::new(HashMap::new());
+
+    tray.watch().subscribe(move |items| {
+        let mut icons = icons.borrow_mut();
+
+        icons.retain(|id, (image, _)| {
+            let registered = items.iter().any(|item| &item.id == id);
+            if !registered {
+                container.remove(image);
+            }
+            registered
+        });
+
+        for item in items {
+            let (image, current) = icons.entry(item.id.clone()).or_insert_with(|| {
+                let icon = tray_icon(&tray, item);
+                container.append(&icon.0);
+                icon
+            });
+
+            current.replace(item.clone());
+            update_icon(image, item);
+        }
+    });
+
+    Tag::Box(tag)
+}
@@ -0,0 +1,217 @@
+use std::{cell::Cell, rc::Rc};
+
+use gtk4::{
+    glib::Propagation,
+    prelude::{BoxExt, ButtonExt, EditableExt, GtkWindowExt, WidgetExt},
+    Align, Application, ApplicationWindow, Box, Button, Image, Label, Orientation, PasswordEntry,
+    Switch,
+};
+use gtk4_layer_shell::{KeyboardMode, Layer, LayerShell};
+
+use crate::{
+    sources::{AccessPoint, Wifi},
+    widgets::Tag,
+    Wayland,
+};
+
+/// Asks for the passphrase of a secured network in a small window, then connects to it.
+fn prompt_passphrase(factory: &Application, wifi: &Wifi, ssid: &str) {
+    let content = Box::new(Orientation::Vertical, 6);
+    content.set_widget_name("wifi-prompt");
+
+    let title = Label::new(Some(&format!("Passphrase for {}", ssid)));
+    title.add_css_class("title");
+
+    let entry = PasswordEntry::new();
+    entry.set_show_peek_icon(true);
+    entry.add_css_class("passphrase");
+
+    let buttons = Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(Align::End);
+
+    let cancel = Button::with_label("Cancel");
+    cancel.add_css_class("cancel");
+
+    let connect = Button::with_label("Connect");
+    connect.add_css_class("connect");
+
+    buttons.append(&cancel);
+    buttons.append(&connect);
+
+    content.append(&title);
+    content.append(&entry);
+    content.append(&buttons);
+
+    let prompt = ApplicationWindow::builder()
+        .application(factory)
+        .title(format!("Wi-Fi: {}", ssid))
+        .child(&content)
+        .build();
+
+    if Wayland::detect_wayland() {
+        prompt.init_layer_shell();
+        prompt.set_layer(Layer::Overlay);
+        // The entry has to receive key presses, which layer surfaces don't get by default.
+        prompt.set_keyboard_mode(KeyboardMode::Exclusive);
+    }
+
+    prompt.set_decorated(false);
+    prompt.set_resizable(false);
+
+    let submit = {
+        let (prompt, entry, wifi, ssid) = (
+            prompt.clone(),
+            entry.clone(),
+            wifi.clone(),
+            ssid.to_string(),
+        );
+
+        move || {
+            let passphrase = entry.text();
+
+            if !passphrase.is_empty() {
+                wifi.connect(&ssid, Some(&passphrase));
+                prompt.destroy();
+            }
+        }
+    };
+
+    let on_activate = submit.clone();
+    entry.connect_activate(move |_| on_activate());
+    connect.connect_clicked(move |_| submit());
+
+    let window = prompt.clone();
+    cancel.connect_clicked(move |_| window.destroy());
+
+    prompt.present();
+    entry.grab_focus();
+}
+
+fn network_button(factory: &Application, wifi: &Wifi, access_point: &AccessPoint) -> Button {
+    let content = Box::new(Orientation::Horizontal, 6);
+
+    let ssid = Label::new(Some(&access_point.ssid));
+    ssid.add_css_class("ssid");
+    ssid.set_hexpand(true);
+    ssid.set_xalign(0.0);
+
+    let strength = Label::new(Some(&format!("{}%", access_point.strength)));
+    strength.add_css_class("strength");
+
+    content.append(&ssid);
+    content.append(&strength);
+
+    if access_point.secured {
+        let lock = Image::from_icon_name("network-wireless-encrypted-symbolic");
+        lock.add_css_class("lock");
+        content.append(&lock);
+    }
+
+    let button = Button::new();
+    button.set_child(Some(&content));
+    button.add_css_class("network");
+
+    for (class, set) in [
+        ("secured", access_point.secured),
+        ("known", access_point.known),
+        ("active", access_point.active),
+    ] {
+        if set {
+            button.add_css_class(class);
+        }
+    }
+
+    let (factory, wifi, access_point) = (factory.clone(), wifi.clone(), access_point.clone());
+    button.connect_clicked(move |_| {
+        if access_point.active {
+            wifi.disconnect();
+        } else if access_point.known || !access_point.secured {
+            wifi.connect(&access_point.ssid, None);
+        } else {
+            prompt_passphrase(&factory, &wifi, &access_point.ssid);
+        }
+    });
+
+    button
+}
+
+/// Creates a Wi-Fi picker with a specified CSS class name, listing every visible network by
+/// signal strength, along with a switch for the radio and a rescan button. Clicking a network
+/// connects to it, asking for a passphrase first if it is secured and not yet known; clicking
+/// the connected network disconnects.
+///
+/// The header has a CSS class of "header", and each network button has "network", plus
+/// "secured", "known" and "active" as they apply. The passphrase prompt is named "wifi-prompt".
+/// When an action fails, the reason is shown below the header, in a label of class "error".
+pub fn tag_wifi(class_name: &str, factory: Application) -> Tag {
+    let tag = Box::new(Orientation::Vertical, 4);
+
+    tag.set_widget_name(class_name);
+
+    let wifi = Wifi::new();
+
+    let header = Box::new(Orientation::Horizontal, 6);
+    header.add_css_class("header");
+
+    let radio = Switch::new();
+    radio.add_css_class("radio");
+    radio.set_hexpand(true);
+    radio.set_halign(Align::Start);
+
+    let rescan = Button::from_icon_name("view-refresh-symbolic");
+    rescan.add_css_class("rescan");
+
+    header.append(&radio);
+    header.append(&rescan);
+
+    let error = Label::new(None);
+    error.add_css_class("error");
+    error.set_visible(false);
+
+    let list = Box::new(Orientation::Vertical, 2);
+    list.add_css_class("networks");
+
+    tag.append(&header);
+    tag.append(&error);
+    tag.append(&list);
+
+    let scanner = wifi.clone();
+    rescan.connect_clicked(move |_| scanner.rescan());
+
+    // Set while the switch is updated from NetworkManager, so it doesn't echo it back.
+    let syncing = Rc::new(Cell::new(false));
+
+    let (toggler, sync_flag) = (wifi.clone(), Rc::clone(&syncing));
+    radio.connect_state_set(move |_, enabled| {
+        if !sync_flag.get() {
+            toggler.set_enabled(enabled);
+        }
+        Propagation::Proceed
+    });
+
+    let (state, picker) = (wifi.watch(), wifi.clone());
+    state.subscribe(move |state| {
+        syncing.set(true);
+        radio.set_active(state.enabled);
+        syncing.set(false);
+
+        error.set_visible(state.error.is_some());
+        error.set_text(
+            &state
+                .error
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        );
+
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+
+        for access_point in &state.access_points {
+            list.append(&network_button(&factory, &picker, access_point));
+        }
+    });
+
+    Tag::Box(tag)
+}
@@ -0,0 +1,666 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    future::Future,
+    io::{BufRead, BufReader},
+    mem,
+    process::{Command, Stdio},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use gio::glib::{clone::Downgrade, ControlFlow, MainContext};
+use gtk4::{prelude::WidgetExt, Widget};
+
+use crate::{
+    scheduler::{schedule, Interval},
+    widgets::Tag,
+    worker::{AsyncFetch, ThreadedFetch},
+    Internal, Text, Threshold,
+};
+
+type Subscriber<T> = Rc<dyn Fn(&T)>;
+
+thread_local! {
+    /// Hands the values every listened-to channel received over to its receiver, by id.
+    static LISTENERS: RefCell<HashMap<usize, Rc<dyn Fn()>>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_LISTENER: AtomicUsize = AtomicUsize::new(0);
+
+/// Hands a channel's values over on the main thread, once its thread woke the main loop. Stops
+/// listening if its channel is done.
+fn wake_listener(id: usize, done: bool) {
+    let listener = LISTENERS.with(|listeners| {
+        let mut listeners = listeners.borrow_mut();
+
+        if done {
+            listeners.remove(&id)
+        } else {
+            listeners.get(&id).cloned()
+        }
+    });
+
+    // Called once the map is released, as subscribers may start listening to other channels.
+    if let Some(listener) = listener {
+        listener();
+    }
+}
+
+/// Calls `receive_fn` on the main thread with every value a channel receives, in order. A thread
+/// waits on the channel and wakes the main loop as values arrive, handing over every value that
+/// arrived since it last did.
+pub(crate) fn receive<T, F>(receiver: Receiver<T>, receive_fn: F)
+where
+    T: Send + 'static,
+    F: Fn(Vec<T>) + 'static,
+{
+    let queued = Arc::new(Mutex::new(Vec::new()));
+    let id = NEXT_LISTENER.fetch_add(1, Ordering::Relaxed);
+
+    let pending = Arc::clone(&queued);
+
+    LISTENERS.with(|listeners| {
+        listeners.borrow_mut().insert(
+            id,
+            Rc::new(move || {
+                let values = pending
+                    .lock()
+                    .map(|mut pending| mem::take(&mut *pending))
+                    .unwrap_or_default();
+
+                if !values.is_empty() {
+                    receive_fn(values);
+                }
+            }),
+        )
+    });
+
+    let context = MainContext::ref_thread_default();
+
+    thread::spawn(move || {
+        while let Ok(value) = receiver.recv() {
+            let Ok(mut pending) = queued.lock() else {
+                break;
+            };
+
+            pending.push(value);
+
+            if pending.len() == 1 {
+                context.spawn(async move { wake_listener(id, false) });
+            }
+        }
+
+        context.spawn(async move { wake_listener(id, true) });
+    });
+}
+
+struct Inner<T> {
+    value: RefCell<T>,
+    subscribers: RefCell<Vec<Subscriber<T>>>,
+    observers: RefCell<Vec<Box<dyn Fn() -> bool>>>,
+    refreshers: RefCell<Vec<Rc<dyn Fn()>>>,
+    stale: Cell<bool>,
+}
+
+/// A shared, observable value that Tags can bind to, similar to eww's variables.
+///
+/// A `Variable` is either polled with a closure, listened to through a channel or a command's
+/// output, set by hand, or derived from other variables. Every Tag bound to the same `Variable`
+/// shares a single source, and subscribers are only notified when the value actually changes.
+///
+/// Polled variables are suspended while every Tag bound to them (directly, or through derived
+/// variables) is hidden, and refresh as soon as one is shown again.
+///
+/// Variables live on the GTK main thread; cloning one returns another handle to the same value.
+pub struct Variable<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T> Clone for Variable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> Variable<T> {
+    /// Creates a plain `Variable` that only changes when `set` is called.
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                value: RefCell::new(value),
+                subscribers: RefCell::new(Vec::new()),
+                observers: RefCell::new(Vec::new()),
+                refreshers: RefCell::new(Vec::new()),
+                stale: Cell::new(false),
+            }),
+        }
+    }
+
+    /// Creates a `Variable` that is updated by calling a closure at a given interval.
+    pub fn poll<F>(interval: impl Into<Interval>, poll_fn: F) -> Self
+    where
+        F: Fn() -> T + 'static,
+    {
+        let variable = Self::new(poll_fn());
+        let poller = variable.clone();
+
+        variable.schedule_poll(interval.into(), move || poller.set(poll_fn()));
+
+        variable
+    }
+
+    /// Creates a `Variable` that is polled on a worker thread at a given interval, so that
+    /// blocking fetchers don't freeze the UI. Polls are skipped while the previous one is still
+    /// running, and results arriving after `timeout` seconds are dropped.
+    pub fn poll_threaded<F>(
+        value: T,
+        interval: impl Into<Interval>,
+        timeout: u32,
+        fetch_fn: F,
+    ) -> Self
+    where
+        T: Send,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        let variable = Self::new(value);
+        let poller = variable.clone();
+        let fetch = ThreadedFetch::new(fetch_fn, Duration::from_secs(timeout.into()));
+
+        let update = move || {
+            let poller = poller.clone();
+
+            fetch.run(move |value| poller.set(value));
+        };
+
+        update();
+
+        variable.schedule_poll(interval.into(), update);
+
+        variable
+    }
+
+    /// Creates a `Variable` that is polled by running a future on the GLib main context at a
+    /// given interval. Polls are skipped while the previous future is pending, and futures
+    /// taking longer than `timeout` seconds are cancelled.
+    pub fn poll_async<F, Fut>(
+        value: T,
+        interval: impl Into<Interval>,
+        timeout: u32,
+        fetch_fn: F,
+    ) -> Self
+    where
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = T> + 'static,
+    {
+        let variable = Self::new(value);
+        let poller = variable.clone();
+        let fetch = AsyncFetch::new(fetch_fn, Duration::from_secs(timeout.into()));
+
+        let update = move || {
+            let poller = poller.clone();
+
+            fetch.run(move |value| poller.set(value));
+        };
+
+        update();
+
+        variable.schedule_poll(interval.into(), update);
+
+        variable
+    }
+
+    /// Creates a `Variable` fed by the receiving end of a channel, usually written to from
+    /// another thread. Stops listening once every sender has been dropped.
+    ///
+    /// A thread waits on the channel and wakes the main loop as values arrive, so nothing runs
+    /// while the channel is quiet.
+    pub fn listen(value: T, receiver: Receiver<T>) -> Self
+    where
+        T: Send,
+    {
+        let variable = Self::new(value);
+        let listener = variable.clone();
+
+        // Values arriving before the main loop gets to them replace each other, so only the
+        // latest one is set.
+        receive(receiver, move |values| {
+            if let Some(value) = values.into_iter().last() {
+                listener.set(value);
+            }
+        });
+
+        variable
+    }
+
+    /// Creates a `Variable` fed by every line a shell command writes to stdout,
+    /// e.g. `pactl subscribe` or `playerctl --follow metadata title`.
+    pub fn listen_command<F>(value: T, command: &str, parse_fn: F) -> Self
+    where
+        T: Send,
+        F: Fn(&str) -> T + Send + 'static,
+    {
+        let (sender, receiver) = channel::<T>();
+        let command = command.to_string();
+
+        thread::spawn(move || {
+            let child = Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .stdout(Stdio::piped())
+                .spawn();
+
+            if let Ok(mut child) = child {
+                if let Some(stdout) = child.stdout.take() {
+                    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                        if sender.send(parse_fn(&line)).is_err() {
+                            break;
+                        }
+                    }
+                }
+
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        });
+
+        Self::listen(value, receiver)
+    }
+
+    /// Returns a copy of the current value.
+    pub fn get(&self) -> T {
+        self.inner.value.borrow().clone()
+    }
+
+    /// Sets the value, notifying subscribers if it changed.
+    pub fn set(&self, value: T) {
+        if *self.inner.value.borrow() == value {
+            return;
+        }
+
+        self.inner.value.replace(value.clone());
+
+        let subscribers = self.inner.subscribers.borrow().clone();
+
+        for subscriber in subscribers {
+            subscriber(&value);
+        }
+    }
+
+    /// Calls a closure with the current value, and again every time the value changes.
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: Fn(&T) + 'static,
+    {
+        self.subscribe_observed(callback, || true);
+    }
+
+    /// Subscribes a closure, which only counts as observing the value while `observed_fn`
+    /// returns true.
+    fn subscribe_observed<F, O>(&self, callback: F, observed_fn: O)
+    where
+        F: Fn(&T) + 'static,
+        O: Fn() -> bool + 'static,
+    {
+        callback(&self.get());
+
+        self.inner.subscribers.borrow_mut().push(Rc::new(callback));
+        self.inner
+            .observers
+            .borrow_mut()
+            .push(Box::new(observed_fn));
+    }
+
+    /// Subscribes a closure on behalf of a widget, which only observes the value while mapped.
+    fn subscribe_widget<F>(&self, widget: &Widget, callback: F)
+    where
+        F: Fn(&T) + 'static,
+    {
+        let weak = widget.downgrade();
+        self.subscribe_observed(callback, move || {
+            weak.upgrade().is_some_and(|widget| widget.is_mapped())
+        });
+
+        let variable = self.clone();
+        widget.connect_map(move |_| variable.refresh());
+    }
+
+    /// Returns true if anything observes the value. Variables without any subscribers are
+    /// considered observed, so their value stays current.
+    fn is_observed(&self) -> bool {
+        let observers = self.inner.observers.borrow();
+
+        observers.is_empty() || observers.iter().any(|observed_fn| observed_fn())
+    }
+
+    /// Runs an update at the given interval while the value is observed. Updates skipped in the
+    /// meantime are caught up on by `refresh`.
+    fn schedule_poll<F>(&self, interval: Interval, update: F)
+    where
+        F: Fn() + 'static,
+    {
+        schedule(interval, self.suspendable(update));
+    }
+
+    /// Wraps an update into a scheduler task, which only runs it while the value is observed
+    /// and otherwise marks the value stale, for `refresh` to catch up on.
+    fn suspendable<F>(&self, update: F) -> impl Fn() -> ControlFlow
+    where
+        F: Fn() + 'static,
+    {
+        let update = Rc::new(update);
+
+        let (variable, refresh) = (self.clone(), Rc::clone(&update));
+        self.inner.refreshers.borrow_mut().push(Rc::new(move || {
+            if variable.inner.stale.replace(false) {
+                refresh();
+            }
+        }));
+
+        let variable = self.clone();
+        move || {
+            if variable.is_observed() {
+                update();
+            } else {
+                variable.inner.stale.set(true);
+            }
+
+            ControlFlow::Continue
+        }
+    }
+
+    /// Catches up on any update that was skipped while the value wasn't observed.
+    fn refresh(&self) {
+        let refreshers = self.inner.refreshers.borrow().clone();
+
+        for refresher in refreshers {
+            refresher();
+        }
+    }
+
+    /// Derives a new `Variable` from this one, which updates whenever this one changes.
+    pub fn map<U, F>(&self, map_fn: F) -> Variable<U>
+    where
+        U: Clone + PartialEq + 'static,
+        F: Fn(&T) -> U + 'static,
+    {
+        let derived = Variable::new(map_fn(&self.get()));
+
+        let (target, observer) = (derived.clone(), derived.clone());
+        self.subscribe_observed(
+            move |value| target.set(map_fn(value)),
+            move || observer.is_observed(),
+        );
+
+        let source = self.clone();
+        derived
+            .inner
+            .refreshers
+            .borrow_mut()
+            .push(Rc::new(move || source.refresh()));
+
+        derived
+    }
+
+    /// Derives a new `Variable` from this one and another, which updates whenever either changes.
+    pub fn combine<U, V, F>(&self, other: &Variable<U>, combine_fn: F) -> Variable<V>
+    where
+        U: Clone + PartialEq + 'static,
+        V: Clone + PartialEq + 'static,
+        F: Fn(&T, &U) -> V + 'static,
+    {
+        let derived = Variable::new(combine_fn(&self.get(), &other.get()));
+        let combine_fn = Rc::new(combine_fn);
+
+        let (target, other_source, combine) = (derived.clone(), other.clone(), combine_fn.clone());
+        let observer = derived.clone();
+        self.subscribe_observed(
+            move |value| target.set(combine(value, &other_source.get())),
+            move || observer.is_observed(),
+        );
+
+        let (target, source) = (derived.clone(), self.clone());
+        let observer = derived.clone();
+        other.subscribe_observed(
+            move |value| target.set(combine_fn(&source.get(), value)),
+            move || observer.is_observed(),
+        );
+
+        let (source, other_source) = (self.clone(), other.clone());
+        derived.inner.refreshers.borrow_mut().push(Rc::new(move || {
+            source.refresh();
+            other_source.refresh();
+        }));
+
+        derived
+    }
+
+    /// Binds the text of a Tag to this `Variable` through a formatter.
+    pub fn bind<F, U>(&self, tag: &Tag, format_fn: F)
+    where
+        F: Fn(&T) -> U + 'static,
+        U: Into<Text>,
+    {
+        let widget = tag.widget();
+        let tag = tag.clone();
+
+        self.subscribe_widget(&widget, move |value| {
+            Internal::static_widget(&tag, format_fn(value))
+        });
+    }
+
+    /// Binds the CSS classes of a Tag to this `Variable` through a list of thresholds.
+    pub fn bind_thresholds<F>(&self, tag: &Tag, value_fn: F, thresholds: Vec<Threshold>)
+    where
+        F: Fn(&T) -> f64 + 'static,
+    {
+        let widget = tag.widget();
+        let tag = tag.clone();
+
+        self.subscribe_widget(&widget, move |value| {
+            Internal::static_thresholds(&tag, value_fn(value), &thresholds)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Iterates a main context until `done` returns true, or panics after a few seconds.
+    fn iterate_until(context: &MainContext, done: impl Fn() -> bool) {
+        let started = Instant::now();
+
+        while !done() {
+            assert!(started.elapsed() < Duration::from_secs(5), "timed out");
+
+            context.iteration(false);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn record<T: Clone + PartialEq + 'static>(variable: &Variable<T>) -> Rc<RefCell<Vec<T>>> {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let recorder = Rc::clone(&seen);
+        variable.subscribe(move |value| recorder.borrow_mut().push(value.clone()));
+
+        seen
+    }
+
+    #[test]
+    fn notifies_subscribers_only_on_change() {
+        let variable = Variable::new(1);
+        let seen = record(&variable);
+
+        variable.set(1);
+        variable.set(2);
+        variable.set(2);
+        variable.set(3);
+
+        assert_eq!(variable.get(), 3);
+        assert_eq!(*seen.borrow(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn maps_values_and_skips_unchanged_results() {
+        let percent = Variable::new(42.0);
+        let level = percent.map(|percent: &f64| (*percent / 25.0) as u8);
+        let seen = record(&level);
+
+        percent.set(45.0);
+        percent.set(80.0);
+
+        assert_eq!(level.get(), 3);
+        assert_eq!(*seen.borrow(), [1, 3]);
+    }
+
+    #[test]
+    fn combines_two_variables() {
+        let used = Variable::new(2u64);
+        let total = Variable::new(8u64);
+        let label = used.combine(&total, |used, total| format!("{}/{}", used, total));
+
+        used.set(4);
+        assert_eq!(label.get(), "4/8");
+
+        total.set(16);
+        assert_eq!(label.get(), "4/16");
+    }
+
+    #[test]
+    fn listens_to_the_latest_value_of_a_channel() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let (sender, receiver) = channel();
+                let variable = Variable::listen(0, receiver);
+                let seen = record(&variable);
+
+                sender.send(1).unwrap();
+                sender.send(2).unwrap();
+                iterate_until(&context, || variable.get() == 2);
+
+                sender.send(3).unwrap();
+                drop(sender);
+                iterate_until(&context, || {
+                    LISTENERS.with(|listeners| listeners.borrow().is_empty())
+                });
+
+                assert_eq!(variable.get(), 3);
+                assert_eq!(seen.borrow().first(), Some(&0));
+                assert_eq!(seen.borrow().last(), Some(&3));
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn receives_every_value_of_a_channel_in_order() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let (sender, receiver) = channel();
+                let received = Rc::new(RefCell::new(Vec::new()));
+
+                let target = Rc::clone(&received);
+                receive(receiver, move |values| target.borrow_mut().extend(values));
+
+                for value in 1..=100 {
+                    sender.send(value).unwrap();
+                }
+                drop(sender);
+
+                iterate_until(&context, || {
+                    LISTENERS.with(|listeners| listeners.borrow().is_empty())
+                });
+
+                assert_eq!(*received.borrow(), (1..=100).collect::<Vec<_>>());
+            })
+            .unwrap();
+    }
+
+    /// Subscribes to a variable as a widget would, observing it only while `shown` is set.
+    fn watch<T: Clone + PartialEq + 'static>(variable: &Variable<T>) -> Rc<Cell<bool>> {
+        let shown = Rc::new(Cell::new(true));
+
+        let observed = Rc::clone(&shown);
+        variable.subscribe_observed(|_| {}, move || observed.get());
+
+        shown
+    }
+
+    #[test]
+    fn suspends_updates_while_unobserved_and_catches_up_once() {
+        let variable = Variable::new(0);
+        let updates = Rc::new(Cell::new(0));
+
+        let counter = Rc::clone(&updates);
+        let tick = variable.suspendable(move || counter.set(counter.get() + 1));
+        let shown = watch(&variable);
+
+        tick();
+        assert_eq!(updates.get(), 1);
+
+        shown.set(false);
+        tick();
+        tick();
+        assert_eq!(updates.get(), 1);
+
+        variable.refresh();
+        variable.refresh();
+        assert_eq!(updates.get(), 2);
+
+        shown.set(true);
+        tick();
+        assert_eq!(updates.get(), 3);
+    }
+
+    #[test]
+    fn refreshes_nothing_when_no_update_was_missed() {
+        let variable = Variable::new(0);
+        let updates = Rc::new(Cell::new(0));
+
+        let counter = Rc::clone(&updates);
+        let tick = variable.suspendable(move || counter.set(counter.get() + 1));
+        watch(&variable);
+
+        tick();
+        variable.refresh();
+        assert_eq!(updates.get(), 1);
+    }
+
+    #[test]
+    fn derived_variables_keep_their_sources_observed() {
+        let source = Variable::new(1);
+        let updates = Rc::new(Cell::new(0));
+
+        let counter = Rc::clone(&updates);
+        let tick = source.suspendable(move || counter.set(counter.get() + 1));
+        let doubled = source.map(|value| value * 2);
+        let shown = watch(&doubled);
+
+        shown.set(false);
+        tick();
+        assert!(!source.is_observed());
+        assert_eq!(updates.get(), 0);
+
+        shown.set(true);
+        assert!(source.is_observed());
+
+        // Showing a derived variable's widget refreshes through to the polled source.
+        doubled.refresh();
+        assert_eq!(updates.get(), 1);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::Wayland;
+use crate::{
+    widgets::{Animator, Transition},
+    Wayland,
+};
 
 use gio::prelude::Cast;
 use gtk4::{
@@ -17,6 +20,20 @@ pub enum Tag {
     Undefined,
 }
 
+impl Tag {
+    /// Returns the underlying GTK4 widget of the Tag.
+    pub fn widget(&self) -> Widget {
+        match self {
+            Tag::Label(label) => label.clone().upcast::<Widget>(),
+            Tag::Box(box_) => box_.clone().upcast::<Widget>(),
+            Tag::Button(button) => button.clone().upcast::<Widget>(),
+            Tag::Revealer(revealer) => revealer.clone().upcast::<Widget>(),
+            Tag::Scroller(scroller) => scroller.clone().upcast::<Widget>(),
+            Tag::Undefined => panic!("Tag is undefined!"),
+        }
+    }
+}
+
 /// Represents a GTK4 window with a configuration for positioning/display on Wayland.
 /// The tag represents a text box with a CSS class name for styling.
 #[derive(Clone)]
@@ -28,6 +45,7 @@ pub struct Chunk {
     anchors: Vec<(Edge, bool)>,
     layer: Layer,
     resize: bool,
+    transition: Transition,
     chunk: Option<ApplicationWindow>,
 }
 
@@ -49,10 +67,17 @@ impl Chunk {
             anchors,
             layer,
             resize,
+            transition: Transition::none(),
             chunk: None,
         }
     }
 
+    /// Sets how the `Chunk` animates as it first shows. Defaults to no animation.
+    pub fn transition(mut self, transition: Transition) -> Self {
+        self.transition = transition;
+        self
+    }
+
     pub fn set_dimensions(&self, width: u32, height: u32) {
         if let Some(chunk) = &self.chunk {
             chunk.set_default_size(width as i32, height as i32);
@@ -89,7 +114,7 @@ impl Chunk {
         }
 
         chunk.set_decorated(false);
-        chunk.present();
+        Animator::new(&chunk, self.transition).show();
 
         self.chunk = Some(chunk);
         self
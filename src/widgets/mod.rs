@@ -1,11 +1,17 @@
 mod chunk;
 mod plate;
 mod slab;
+mod stack;
+mod transition;
 
 pub use chunk::Chunk;
 pub use chunk::Tag;
 pub use plate::Plate;
-pub use slab::Slab;
+pub use slab::{Slab, SlabHandle};
+pub use stack::Stack;
+pub use transition::{Easing, Transition};
+
+pub(crate) use transition::Animator;
 
 pub mod builder {
     pub trait Builder {
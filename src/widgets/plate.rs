@@ -1,12 +1,18 @@
-use crate::{widgets::Tag, Builder, Wayland};
+use crate::{
+    widgets::{Animator, Stack, Tag, Transition},
+    Builder, Wayland,
+};
 
 use std::time::Duration;
 
 use gio::{
-    glib::{clone::Downgrade, timeout_add_local, ControlFlow},
+    glib::{timeout_add_local, ControlFlow},
     prelude::Cast,
 };
-use gtk4::{prelude::GtkWindowExt, Application, ApplicationWindow, Widget};
+use gtk4::{
+    prelude::{GtkWindowExt, WidgetExt},
+    Application, ApplicationWindow, Widget,
+};
 use gtk4_layer_shell::{Edge, Layer};
 
 pub struct Plate {
@@ -16,6 +22,8 @@ pub struct Plate {
     margins: Vec<(Edge, i32)>,
     anchors: Vec<(Edge, bool)>,
     duration: u64,
+    stack: Option<Stack>,
+    transition: Transition,
 }
 
 impl Plate {
@@ -35,8 +43,22 @@ impl Plate {
             margins,
             anchors,
             duration,
+            stack: None,
+            transition: Transition::none(),
         }
     }
+
+    /// Lines the `Plate` up with the other popups in a `Stack` until it closes.
+    pub fn stack(mut self, stack: &Stack) -> Self {
+        self.stack = Some(stack.clone());
+        self
+    }
+
+    /// Sets how the `Plate` animates as it shows and closes. Defaults to no animation.
+    pub fn transition(mut self, transition: Transition) -> Self {
+        self.transition = transition;
+        self
+    }
 }
 
 impl Builder for Plate {
@@ -67,15 +89,21 @@ impl Builder for Plate {
         plate.set_decorated(false);
         plate.set_resizable(false);
 
-        plate.present();
+        let animator = Animator::new(&plate, self.transition);
+
+        if let Some(stack) = self.stack {
+            let evicted = animator.clone();
+            stack.show(&plate, move || evicted.close());
+
+            plate.connect_destroy(move |window| stack.hide(window));
+        }
+
+        animator.show();
 
         let duration = Duration::from_secs(self.duration);
 
-        let plate_weak = plate.downgrade();
         timeout_add_local(duration, move || {
-            if let Some(window) = plate_weak.upgrade() {
-                window.destroy();
-            }
+            animator.close();
             ControlFlow::Break
         });
     }
@@ -1,8 +1,12 @@
-use std::time::Duration;
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+    time::Duration,
+};
 
 use gio::{
-    glib::{timeout_add_local, ControlFlow, MainContext},
-    prelude::{Cast, ObjectExt},
+    glib::{timeout_future, JoinHandle, MainContext, Object, WeakRef},
+    prelude::{Cast, IsA, ObjectExt},
 };
 use gtk4::{
     prelude::{GtkWindowExt, WidgetExt},
@@ -10,7 +14,10 @@ use gtk4::{
 };
 use gtk4_layer_shell::{Edge, Layer};
 
-use crate::{widgets::Tag, Builder, Wayland};
+use crate::{
+    widgets::{Animator, Stack, Tag, Transition},
+    Builder, DoNotDisturb, Variable, Wayland,
+};
 
 pub struct Slab {
     factory: Application,
@@ -19,6 +26,8 @@ pub struct Slab {
     margins: Vec<(Edge, i32)>,
     anchors: Vec<(Edge, bool)>,
     duration: u64,
+    stack: Option<Stack>,
+    transition: Transition,
 }
 
 impl Slab {
@@ -38,33 +47,25 @@ impl Slab {
             margins,
             anchors,
             duration,
+            stack: None,
+            transition: Transition::none(),
         }
     }
 
-    fn process_events(depth: u32, max_depth: u32) {
-        if depth >= max_depth {
-            return;
-        }
-
-        if MainContext::default().iteration(false) {
-            Self::process_events(depth + 1, max_depth);
-        }
+    /// Lines the `Slab` up with the other popups in a `Stack` while it is shown.
+    pub fn stack(mut self, stack: &Stack) -> Self {
+        self.stack = Some(stack.clone());
+        self
     }
 
-    fn handle_visibility(window: &ApplicationWindow, visible: bool) {
-        if visible {
-            window.present();
-            window.set_visible(true);
-        } else {
-            window.hide();
-        }
-        Self::process_events(0, 10);
+    /// Sets how the `Slab` animates as it shows and hides. Defaults to no animation.
+    pub fn transition(mut self, transition: Transition) -> Self {
+        self.transition = transition;
+        self
     }
-}
 
-impl Builder for Slab {
-    /// Builds and displays the `Slab` window, which will show whenever the text changes.
-    fn build(self) {
+    /// Builds the `Slab` window like `build`, and returns a `SlabHandle` to show it with.
+    pub fn handle(self) -> SlabHandle {
         let child = match self.tag {
             Tag::Label(label) => label.upcast::<Widget>(),
             Tag::Box(box_) => box_.upcast::<Widget>(),
@@ -91,21 +92,242 @@ impl Builder for Slab {
 
         Wayland::ipc_ignore_window(&self.title);
 
-        let duration = Duration::from_secs(self.duration);
-        let slab_weak = slab.downgrade();
+        let handle = SlabHandle {
+            inner: Rc::new(HandleInner {
+                window: slab.downgrade(),
+                animator: Animator::new(&slab, self.transition),
+                stack: self.stack,
+                timer: Debounce::new(Duration::from_secs(self.duration)),
+            }),
+        };
+
+        if child.find_property("label").is_some() {
+            handle.trigger_on_notify(&child, "label");
+        }
+
+        handle
+    }
+}
+
+impl Builder for Slab {
+    /// Builds and displays the `Slab` window, which will show whenever the text changes, unless
+    /// Do Not Disturb is on.
+    fn build(self) {
+        self.handle();
+    }
+}
+
+/// Runs a closure once a duration passes without the timer being restarted, as a Slab hides
+/// once it hasn't been triggered for its duration.
+struct Debounce {
+    duration: Duration,
+    /// The pending run, cancelled whenever the timer is restarted.
+    pending: Rc<RefCell<Option<JoinHandle<()>>>>,
+}
+
+impl Debounce {
+    fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            pending: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Runs `expired_fn` once the duration passes, instead of any closure pending before.
+    fn restart(&self, expired_fn: impl FnOnce() + 'static) {
+        self.cancel();
+
+        let (duration, pending) = (self.duration, Rc::downgrade(&self.pending));
+        let run = MainContext::ref_thread_default().spawn_local(async move {
+            timeout_future(duration).await;
+
+            // The run is finishing, so it must not be cancelled again.
+            if let Some(pending) = pending.upgrade() {
+                pending.take();
+            }
+            expired_fn();
+        });
+
+        self.pending.replace(Some(run));
+    }
+
+    fn cancel(&self) {
+        if let Some(run) = self.pending.take() {
+            run.abort();
+        }
+    }
+}
+
+struct HandleInner {
+    window: WeakRef<ApplicationWindow>,
+    animator: Animator,
+    stack: Option<Stack>,
+    /// Hides the Slab, unless it is triggered again first.
+    timer: Debounce,
+}
+
+/// Shows a built `Slab`, from any source rather than only when its text changes.
+///
+/// Cloning a `SlabHandle` returns another handle to the same Slab.
+#[derive(Clone)]
+pub struct SlabHandle {
+    inner: Rc<HandleInner>,
+}
+
+impl SlabHandle {
+    fn upgrade(inner: &Weak<HandleInner>) -> Option<Self> {
+        inner.upgrade().map(|inner| Self { inner })
+    }
+
+    /// Shows the Slab for its duration. Triggering it while it is shown keeps it up for another
+    /// full duration, so rapid changes don't hide it early. Does nothing while Do Not Disturb
+    /// is on.
+    pub fn trigger(&self) {
+        let Some(window) = self.inner.window.upgrade() else {
+            return;
+        };
+
+        if DoNotDisturb::is_enabled() {
+            return;
+        }
+
+        if let Some(stack) = &self.inner.stack {
+            let evicted = Rc::downgrade(&self.inner);
+            stack.show(&window, move || {
+                if let Some(handle) = Self::upgrade(&evicted) {
+                    handle.hide();
+                }
+            });
+        }
 
-        child.connect_notify_local(Some("label"), move |_label, _| {
-            if let Some(window) = slab_weak.upgrade() {
-                Self::handle_visibility(&window, true);
+        self.inner.animator.show();
 
-                let window_weak = window.downgrade();
-                timeout_add_local(duration, move || {
-                    if let Some(window) = window_weak.upgrade() {
-                        Self::handle_visibility(&window, false);
-                    }
-                    ControlFlow::Break
-                });
+        let expired = Rc::downgrade(&self.inner);
+        self.inner.timer.restart(move || {
+            if let Some(handle) = Self::upgrade(&expired) {
+                handle.hide();
             }
         });
     }
+
+    /// Hides the Slab right away, playing its transition out.
+    pub fn hide(&self) {
+        self.inner.timer.cancel();
+
+        // The Slab keeps its place in the stack until it has animated out.
+        let stack = self.inner.stack.clone();
+        self.inner.animator.hide(move |window| {
+            if let Some(stack) = &stack {
+                stack.hide(window);
+            }
+        });
+    }
+
+    /// Triggers the Slab whenever a `Variable` changes, e.g. to show a volume OSD without
+    /// binding the volume to its Tag.
+    pub fn trigger_on<T>(&self, variable: &Variable<T>)
+    where
+        T: Clone + PartialEq + 'static,
+    {
+        let (handle, primed) = (self.clone(), Cell::new(false));
+
+        // Subscribers are called with the current value first, which isn't a change.
+        variable.subscribe(move |_| {
+            if primed.replace(true) {
+                handle.trigger();
+            }
+        });
+    }
+
+    /// Triggers the Slab whenever a property of a widget or any other object changes, e.g. the
+    /// "value" of a Scale.
+    pub fn trigger_on_notify(&self, object: &impl IsA<Object>, property: &str) {
+        let handle = self.clone();
+        object.connect_notify_local(Some(property), move |_, _| handle.trigger());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Instant};
+
+    use super::*;
+
+    /// Iterates a main context for a while, or until `done` returns true.
+    fn iterate(context: &MainContext, duration: Duration, done: impl Fn() -> bool) {
+        let started = Instant::now();
+
+        while !done() && started.elapsed() < duration {
+            context.iteration(false);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Returns a closure counting its run.
+    fn count(runs: &Rc<Cell<u32>>) -> impl FnOnce() {
+        let runs = Rc::clone(runs);
+        move || runs.set(runs.get() + 1)
+    }
+
+    #[test]
+    fn runs_once_the_duration_passes() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let runs = Rc::new(Cell::new(0));
+                let timer = Debounce::new(Duration::from_millis(50));
+
+                timer.restart(count(&runs));
+                iterate(&context, Duration::from_secs(5), || runs.get() > 0);
+                iterate(&context, Duration::from_millis(100), || false);
+
+                assert_eq!(runs.get(), 1);
+                assert!(timer.pending.borrow().is_none());
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn restarting_postpones_the_run_by_a_full_duration() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let runs = Rc::new(Cell::new(0));
+                let timer = Debounce::new(Duration::from_millis(200));
+
+                timer.restart(count(&runs));
+                iterate(&context, Duration::from_millis(120), || false);
+
+                let restarted = Instant::now();
+                timer.restart(count(&runs));
+                iterate(&context, Duration::from_secs(5), || runs.get() > 0);
+
+                // Only the closure passed last runs, a full duration after it was passed.
+                assert!(restarted.elapsed() >= Duration::from_millis(200));
+                iterate(&context, Duration::from_millis(300), || false);
+                assert_eq!(runs.get(), 1);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn cancelling_drops_the_pending_run() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let runs = Rc::new(Cell::new(0));
+                let timer = Debounce::new(Duration::from_millis(20));
+
+                timer.restart(count(&runs));
+                timer.cancel();
+                timer.cancel();
+                iterate(&context, Duration::from_millis(100), || false);
+
+                assert_eq!(runs.get(), 0);
+            })
+            .unwrap();
+    }
 }
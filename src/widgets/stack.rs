@@ -0,0 +1,215 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use gtk4::{prelude::WidgetExt, ApplicationWindow, Orientation};
+use gtk4_layer_shell::{Edge, LayerShell};
+
+use crate::Wayland;
+
+/// A popup in a `Stack`, with the closure that closes it early.
+struct Entry<W = ApplicationWindow> {
+    window: W,
+    close: Rc<dyn Fn()>,
+}
+
+/// Adds a window to the end of the entries unless it is in them already, then removes the
+/// oldest entries beyond `max`, returning them.
+fn push<W: Clone + PartialEq>(
+    entries: &mut Vec<Entry<W>>,
+    window: &W,
+    close: impl Fn() + 'static,
+    max: usize,
+) -> Vec<Entry<W>> {
+    if !entries.iter().any(|entry| &entry.window == window) {
+        entries.push(Entry {
+            window: window.clone(),
+            close: Rc::new(close),
+        });
+    }
+
+    let excess = entries.len().saturating_sub(max);
+    entries.drain(..excess).collect()
+}
+
+/// Removes a window from the entries. Returns false if it wasn't in them.
+fn remove<W: PartialEq>(entries: &mut Vec<Entry<W>>, window: &W) -> bool {
+    let count = entries.len();
+
+    entries.retain(|entry| &entry.window != window);
+    entries.len() != count
+}
+
+/// Returns the margin of every popup, given their sizes along the edge, oldest first.
+fn margins(offset: i32, gap: i32, sizes: &[i32]) -> Vec<i32> {
+    sizes
+        .iter()
+        .scan(offset, |margin, size| {
+            let current = *margin;
+            *margin += size + gap;
+            Some(current)
+        })
+        .collect()
+}
+
+struct Inner {
+    edge: Edge,
+    offset: i32,
+    gap: i32,
+    max: Cell<usize>,
+    entries: RefCell<Vec<Entry>>,
+}
+
+/// Arranges Slabs, Plates and Notifiers that are shown at the same time along an edge of the
+/// screen, so they line up instead of overlapping.
+///
+/// The first popup to show sits `offset` pixels from the edge, and every later one follows
+/// `gap` pixels after the one before it. When a popup closes, the ones after it move up to
+/// fill its place. Once more popups than the maximum are shown, the oldest one closes early.
+///
+/// The stack sets each popup's margin on its edge, overriding the margin it was built with.
+/// Popups should be anchored to that edge. Cloning a `Stack` returns another handle to it.
+#[derive(Clone)]
+pub struct Stack {
+    inner: Rc<Inner>,
+}
+
+impl Stack {
+    /// Creates a new `Stack` along an edge, holding at most 5 popups.
+    pub fn new(edge: Edge, offset: i32, gap: i32) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                edge,
+                offset,
+                gap,
+                max: Cell::new(5),
+                entries: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Sets how many popups are shown at most. Defaults to 5.
+    pub fn max(self, max: usize) -> Self {
+        self.inner.max.set(max.max(1));
+        self
+    }
+
+    /// Adds a window about to be shown to the end of the stack, or reflows the stack if it is
+    /// already in it, e.g. because its size changed. `close` closes the window early, if it is
+    /// pushed out by newer ones.
+    pub(crate) fn show(&self, window: &ApplicationWindow, close: impl Fn() + 'static) {
+        let evicted = push(
+            &mut self.inner.entries.borrow_mut(),
+            window,
+            close,
+            self.inner.max.get(),
+        );
+
+        // Closed once the stack is no longer borrowed, as closing may hide them from it again.
+        for entry in evicted {
+            (entry.close)();
+        }
+
+        self.reflow();
+    }
+
+    /// Removes a window that was hidden or closed, moving the ones after it up.
+    pub(crate) fn hide(&self, window: &ApplicationWindow) {
+        let removed = remove(&mut self.inner.entries.borrow_mut(), window);
+
+        if removed {
+            self.reflow();
+        }
+    }
+
+    /// Moves every window to its place, after the ones shown before it.
+    fn reflow(&self) {
+        if !Wayland::detect_wayland() {
+            return;
+        }
+
+        let orientation = match self.inner.edge {
+            Edge::Left | Edge::Right => Orientation::Horizontal,
+            _ => Orientation::Vertical,
+        };
+
+        let entries = self.inner.entries.borrow();
+        let sizes: Vec<i32> = entries
+            .iter()
+            .map(|entry| entry.window.measure(orientation, -1).1)
+            .collect();
+
+        for (entry, margin) in
+            entries
+                .iter()
+                .zip(margins(self.inner.offset, self.inner.gap, &sizes))
+        {
+            entry.window.set_margin(self.inner.edge, margin);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows(entries: &[Entry<u32>]) -> Vec<u32> {
+        entries.iter().map(|entry| entry.window).collect()
+    }
+
+    #[test]
+    fn lines_popups_up_after_each_other() {
+        assert_eq!(margins(10, 4, &[]), Vec::<i32>::new());
+        assert_eq!(margins(10, 4, &[50]), [10]);
+        assert_eq!(margins(10, 4, &[50, 30, 20]), [10, 64, 98]);
+    }
+
+    #[test]
+    fn moves_later_popups_up_when_one_closes() {
+        let mut entries = Vec::new();
+
+        for window in [1, 2, 3] {
+            push(&mut entries, &window, || {}, 5);
+        }
+
+        let sizes = [50, 30, 20];
+        assert_eq!(margins(10, 4, &sizes), [10, 64, 98]);
+
+        assert!(remove(&mut entries, &2));
+        assert!(!remove(&mut entries, &2));
+        assert_eq!(windows(&entries), [1, 3]);
+        assert_eq!(margins(10, 4, &[sizes[0], sizes[2]]), [10, 64]);
+
+        assert!(remove(&mut entries, &1));
+        assert_eq!(windows(&entries), [3]);
+        assert_eq!(margins(10, 4, &[sizes[2]]), [10]);
+    }
+
+    #[test]
+    fn closes_the_oldest_popups_beyond_the_maximum() {
+        let mut entries = Vec::new();
+        let closed = Rc::new(RefCell::new(Vec::new()));
+
+        for window in [1, 2, 3, 4] {
+            let closed = Rc::clone(&closed);
+            let evicted = push(
+                &mut entries,
+                &window,
+                move || closed.borrow_mut().push(window),
+                3,
+            );
+
+            for entry in evicted {
+                (entry.close)();
+            }
+        }
+
+        assert_eq!(windows(&entries), [2, 3, 4]);
+        assert_eq!(*closed.borrow(), [1]);
+
+        // Showing a popup again keeps its place.
+        assert!(push(&mut entries, &3, || {}, 3).is_empty());
+        assert_eq!(windows(&entries), [2, 3, 4]);
+    }
+}
@@ -0,0 +1,395 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gio::glib::{ControlFlow, WeakRef};
+use gtk4::{
+    prelude::{GtkWindowExt, ObjectExt, StyleContextExt, WidgetExt, WidgetExtManual},
+    ApplicationWindow, CssProvider, Orientation, STYLE_PROVIDER_PRIORITY_APPLICATION,
+};
+use gtk4_layer_shell::{Edge, LayerShell};
+
+use crate::Wayland;
+
+/// How far a scaling transition shrinks a window while it is hidden.
+const SCALE_FROM: f64 = 0.85;
+
+/// The pace of a `Transition`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    /// Starts slowly and speeds up.
+    EaseIn,
+    /// Starts quickly and slows down.
+    #[default]
+    EaseOut,
+    /// Starts and ends slowly.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps linear progress to eased progress, with cubic curves.
+    fn apply(&self, progress: f64) -> f64 {
+        match self {
+            Easing::Linear => progress,
+            Easing::EaseIn => progress.powi(3),
+            Easing::EaseOut => 1.0 - (1.0 - progress).powi(3),
+            Easing::EaseInOut if progress < 0.5 => 4.0 * progress.powi(3),
+            Easing::EaseInOut => 1.0 - (2.0 - 2.0 * progress).powi(3) / 2.0,
+        }
+    }
+}
+
+/// How a Slab, Plate, Chunk or Notifier window animates as it shows and hides: fading in,
+/// sliding in from the edge it is anchored to, growing, or any mix of them. Hiding plays the
+/// same animation backwards, and the window is only hidden once it has finished.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transition {
+    duration: Duration,
+    easing: Easing,
+    fade: bool,
+    slide: bool,
+    scale: bool,
+}
+
+impl Transition {
+    /// Creates a new `Transition` lasting `duration` milliseconds, without any effect yet.
+    pub fn new(duration: u64) -> Self {
+        Self {
+            duration: Duration::from_millis(duration),
+            ..Default::default()
+        }
+    }
+
+    /// Shows and hides windows right away, which is the default.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Fades the window in and out through its opacity.
+    pub fn fade(mut self) -> Self {
+        self.fade = true;
+        self
+    }
+
+    /// Slides the window in from the edge it is anchored to, and back out, through its margin.
+    /// Windows anchored to opposite edges, or to none, don't slide.
+    pub fn slide(mut self) -> Self {
+        self.slide = true;
+        self
+    }
+
+    /// Grows the window's content as it shows, and shrinks it as it hides.
+    pub fn scale(mut self) -> Self {
+        self.scale = true;
+        self
+    }
+
+    /// Sets the pace of the transition. Defaults to `Easing::EaseOut`.
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    fn is_animated(&self) -> bool {
+        !self.duration.is_zero() && (self.fade || self.slide || self.scale)
+    }
+}
+
+/// Returns the edge a window slides in from: one it is anchored to, without the opposite one.
+fn slide_edge(window: &ApplicationWindow) -> Option<Edge> {
+    [
+        (Edge::Top, Edge::Bottom),
+        (Edge::Bottom, Edge::Top),
+        (Edge::Left, Edge::Right),
+        (Edge::Right, Edge::Left),
+    ]
+    .into_iter()
+    .find(|(edge, opposite)| window.is_anchor(*edge) && !window.is_anchor(*opposite))
+    .map(|(edge, _)| edge)
+}
+
+type Finish = Box<dyn FnOnce(&ApplicationWindow)>;
+
+#[derive(Default)]
+struct State {
+    /// Linear progress, from 0 while hidden to 1 while shown.
+    progress: f64,
+    target: f64,
+    last_frame: Option<i64>,
+    ticking: bool,
+    /// Runs once the window has animated out, unless it is shown again first.
+    finish: Option<Finish>,
+    provider: Option<CssProvider>,
+    /// The margin the window rests at on its sliding edge, and the one last set while sliding.
+    margin: Option<(i32, i32)>,
+}
+
+/// Plays a `Transition` on a window as it shows and hides. Showing it while it animates out
+/// turns it around from where it is.
+#[derive(Clone)]
+pub(crate) struct Animator {
+    transition: Transition,
+    window: WeakRef<ApplicationWindow>,
+    state: Rc<RefCell<State>>,
+}
+
+impl Animator {
+    pub(crate) fn new(window: &ApplicationWindow, transition: Transition) -> Self {
+        Self {
+            transition,
+            window: window.downgrade(),
+            state: Rc::new(RefCell::new(State {
+                progress: 1.0,
+                target: 1.0,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Presents the window and animates it in, cancelling an animation out.
+    pub(crate) fn show(&self) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.finish = None;
+            state.target = 1.0;
+
+            if !window.is_visible() {
+                state.progress = 0.0;
+                self.apply(&window, &mut state);
+            }
+        }
+
+        if !window.is_visible() {
+            window.present();
+        }
+
+        self.run(&window);
+    }
+
+    /// Animates the window out, then hides it and calls `then`.
+    pub(crate) fn hide(&self, then: impl FnOnce(&ApplicationWindow) + 'static) {
+        self.leave(Box::new(move |window| {
+            window.hide();
+            then(window);
+        }));
+    }
+
+    /// Animates the window out, then destroys it.
+    pub(crate) fn close(&self) {
+        self.leave(Box::new(|window| window.destroy()));
+    }
+
+    fn leave(&self, finish: Finish) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        if !window.is_visible() {
+            finish(&window);
+            return;
+        }
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.target = 0.0;
+            state.finish = Some(finish);
+        }
+
+        self.run(&window);
+    }
+
+    /// Starts animating towards the target, or settles there right away if there is nothing
+    /// to animate.
+    fn run(&self, window: &ApplicationWindow) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            if self.transition.is_animated() && state.progress != state.target {
+                // Frames only tick while the window is mapped, so it keeps running until done.
+                if !state.ticking {
+                    state.ticking = true;
+                    state.last_frame = None;
+
+                    let animator = self.clone();
+                    window.add_tick_callback(move |window, clock| {
+                        animator.tick(window, clock.frame_time())
+                    });
+                }
+                return;
+            }
+
+            state.progress = state.target;
+            self.apply(window, &mut state);
+        }
+
+        self.finish(window);
+    }
+
+    fn tick(&self, window: &ApplicationWindow, frame_time: i64) -> ControlFlow {
+        let done = {
+            let mut state = self.state.borrow_mut();
+
+            let elapsed = state.last_frame.map_or(0, |last| frame_time - last);
+            state.last_frame = Some(frame_time);
+
+            let step = elapsed as f64 / self.transition.duration.as_micros() as f64;
+            state.progress = if state.target > state.progress {
+                (state.progress + step).min(state.target)
+            } else {
+                (state.progress - step).max(state.target)
+            };
+
+            self.apply(window, &mut state);
+
+            let done = state.progress == state.target;
+            state.ticking = !done;
+            done
+        };
+
+        if done {
+            self.finish(window);
+            ControlFlow::Break
+        } else {
+            ControlFlow::Continue
+        }
+    }
+
+    /// Runs what was waiting for the window to animate out, once nothing borrows the state.
+    fn finish(&self, window: &ApplicationWindow) {
+        let finish = self.state.borrow_mut().finish.take();
+
+        if let Some(finish) = finish {
+            finish(window);
+        }
+    }
+
+    fn apply(&self, window: &ApplicationWindow, state: &mut State) {
+        if !self.transition.is_animated() {
+            return;
+        }
+
+        let value = self.transition.easing.apply(state.progress);
+
+        if self.transition.fade {
+            window.set_opacity(value);
+        }
+
+        if self.transition.scale {
+            if let Some(child) = window.child() {
+                let provider = state.provider.get_or_insert_with(|| {
+                    let provider = CssProvider::new();
+                    child
+                        .style_context()
+                        .add_provider(&provider, STYLE_PROVIDER_PRIORITY_APPLICATION + 1);
+                    provider
+                });
+
+                let scale = SCALE_FROM + (1.0 - SCALE_FROM) * value;
+                provider.load_from_data(&format!("* {{ transform: scale({:.3}); }}", scale));
+            }
+        }
+
+        if self.transition.slide && Wayland::detect_wayland() {
+            if let Some(edge) = slide_edge(window) {
+                // A margin set by anyone else, e.g. a Stack, is where the window rests now.
+                let current = window.margin(edge);
+                let base = match state.margin {
+                    Some((base, applied)) if applied == current => base,
+                    _ => current,
+                };
+
+                let (orientation, allocated) = match edge {
+                    Edge::Left | Edge::Right => (Orientation::Horizontal, window.width()),
+                    _ => (Orientation::Vertical, window.height()),
+                };
+
+                // Windows that haven't been shown yet have no size.
+                let size = match allocated {
+                    0 => window.measure(orientation, -1).1,
+                    size => size,
+                };
+
+                let margin = base - ((1.0 - value) * size as f64).round() as i32;
+                window.set_margin(edge, margin);
+                state.margin = Some((base, margin));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    fn assert_close(left: f64, right: f64) {
+        assert!((left - right).abs() < 1e-9, "{} != {}", left, right);
+    }
+
+    #[test]
+    fn starts_and_ends_where_the_progress_does() {
+        for easing in EASINGS {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn only_ever_moves_forward() {
+        for easing in EASINGS {
+            let values: Vec<f64> = (0..=100)
+                .map(|step| easing.apply(step as f64 / 100.0))
+                .collect();
+
+            assert!(
+                values.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{:?} moves backwards",
+                easing
+            );
+        }
+    }
+
+    #[test]
+    fn follows_cubic_curves() {
+        assert_close(Easing::Linear.apply(0.25), 0.25);
+        assert_close(Easing::EaseIn.apply(0.5), 0.125);
+        assert_close(Easing::EaseOut.apply(0.5), 0.875);
+        assert_close(Easing::EaseInOut.apply(0.25), 0.0625);
+        assert_close(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_close(Easing::EaseInOut.apply(0.75), 0.9375);
+    }
+
+    #[test]
+    fn eases_in_and_out_symmetrically() {
+        for step in 0..=50 {
+            let progress = step as f64 / 100.0;
+
+            assert_close(
+                Easing::EaseInOut.apply(progress),
+                1.0 - Easing::EaseInOut.apply(1.0 - progress),
+            );
+            assert_close(
+                Easing::EaseIn.apply(progress),
+                1.0 - Easing::EaseOut.apply(1.0 - progress),
+            );
+        }
+    }
+
+    #[test]
+    fn animates_only_with_a_duration_and_an_effect() {
+        assert!(!Transition::none().is_animated());
+        assert!(!Transition::new(200).is_animated());
+        assert!(!Transition::new(0).fade().is_animated());
+        assert!(Transition::new(200).fade().is_animated());
+        assert!(Transition::new(200).slide().scale().is_animated());
+    }
+}
@@ -0,0 +1,246 @@
+use std::{cell::Cell, future::Future, rc::Rc, sync::Arc, time::Duration};
+
+use gio::glib::{future_with_timeout, MainContext};
+
+/// Runs a blocking fetcher on the GIO worker pool, handing its result back to the main thread.
+pub(crate) struct ThreadedFetch<F> {
+    fetch_fn: Arc<F>,
+    in_flight: Rc<Cell<bool>>,
+    /// Counts the fetches started, so a fetch only applies its result while it is the latest.
+    generation: Rc<Cell<u64>>,
+    timeout: Duration,
+}
+
+impl<F, T> ThreadedFetch<F>
+where
+    F: Fn() -> T + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    pub(crate) fn new(fetch_fn: F, timeout: Duration) -> Self {
+        Self {
+            fetch_fn: Arc::new(fetch_fn),
+            in_flight: Rc::new(Cell::new(false)),
+            generation: Rc::new(Cell::new(0)),
+            timeout,
+        }
+    }
+
+    /// Starts a fetch, unless the previous one is still running. A fetch taking longer than
+    /// the timeout is given up on: the next one may start, and its result is dropped if the
+    /// fetcher ever returns, e.g. once a hung network mount recovers.
+    pub(crate) fn run(&self, apply_fn: impl FnOnce(T) + 'static) {
+        if self.in_flight.replace(true) {
+            return;
+        }
+
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+
+        let fetch_fn = Arc::clone(&self.fetch_fn);
+        let (in_flight, latest) = (Rc::clone(&self.in_flight), Rc::clone(&self.generation));
+        let timeout = self.timeout;
+
+        MainContext::ref_thread_default().spawn_local(async move {
+            let handle = gio::spawn_blocking(move || fetch_fn());
+            let result = future_with_timeout(timeout, handle).await;
+
+            if latest.get() != generation {
+                return;
+            }
+
+            in_flight.set(false);
+
+            if let Ok(Ok(value)) = result {
+                apply_fn(value);
+            }
+        });
+    }
+}
+
+/// Runs an async fetcher as a future on the GLib main context.
+pub(crate) struct AsyncFetch<F> {
+    fetch_fn: F,
+    in_flight: Rc<Cell<bool>>,
+    timeout: Duration,
+}
+
+impl<F, Fut, T> AsyncFetch<F>
+where
+    F: Fn() -> Fut + 'static,
+    Fut: Future<Output = T> + 'static,
+    T: 'static,
+{
+    pub(crate) fn new(fetch_fn: F, timeout: Duration) -> Self {
+        Self {
+            fetch_fn,
+            in_flight: Rc::new(Cell::new(false)),
+            timeout,
+        }
+    }
+
+    /// Starts a fetch, unless the previous one is still running. The future is cancelled if it
+    /// takes longer than the timeout.
+    pub(crate) fn run(&self, apply_fn: impl FnOnce(T) + 'static) {
+        if self.in_flight.replace(true) {
+            return;
+        }
+
+        let future = (self.fetch_fn)();
+        let in_flight = Rc::clone(&self.in_flight);
+        let timeout = self.timeout;
+
+        MainContext::ref_thread_default().spawn_local(async move {
+            let result = future_with_timeout(timeout, future).await;
+            in_flight.set(false);
+
+            if let Ok(value) = result {
+                apply_fn(value);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc::channel,
+            Mutex,
+        },
+        thread,
+        time::Instant,
+    };
+
+    use super::*;
+
+    /// Iterates a main context for a while, or until `done` returns true.
+    fn iterate(context: &MainContext, duration: Duration, done: impl Fn() -> bool) {
+        let started = Instant::now();
+
+        while !done() && started.elapsed() < duration {
+            context.iteration(false);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn skips_fetches_while_one_is_running() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let calls = Arc::new(AtomicUsize::new(0));
+                let counter = Arc::clone(&calls);
+                let fetch = ThreadedFetch::new(
+                    move || {
+                        thread::sleep(Duration::from_millis(50));
+                        counter.fetch_add(1, Ordering::SeqCst)
+                    },
+                    Duration::from_secs(5),
+                );
+                let results = Rc::new(RefCell::new(Vec::new()));
+
+                for _ in 0..3 {
+                    let results = Rc::clone(&results);
+                    fetch.run(move |value| results.borrow_mut().push(value));
+                }
+
+                iterate(&context, Duration::from_secs(5), || {
+                    !results.borrow().is_empty()
+                });
+
+                assert_eq!(*results.borrow(), [0]);
+                assert_eq!(calls.load(Ordering::SeqCst), 1);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn gives_up_on_fetches_that_time_out() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                // The first fetch hangs until released, like a read from a stale mount.
+                let (release, released) = channel::<()>();
+                let released = Mutex::new(released);
+                let calls = AtomicUsize::new(0);
+                let fetch = ThreadedFetch::new(
+                    move || {
+                        let call = calls.fetch_add(1, Ordering::SeqCst);
+
+                        if call == 0 {
+                            let _ = released.lock().unwrap().recv();
+                        }
+
+                        call
+                    },
+                    Duration::from_millis(50),
+                );
+                let results = Rc::new(RefCell::new(Vec::new()));
+                let run = || {
+                    let results = Rc::clone(&results);
+                    fetch.run(move |value| results.borrow_mut().push(value));
+                };
+
+                run();
+                iterate(&context, Duration::from_millis(200), || false);
+
+                run();
+                iterate(&context, Duration::from_secs(5), || {
+                    !results.borrow().is_empty()
+                });
+                assert_eq!(*results.borrow(), [1]);
+
+                release.send(()).unwrap();
+                iterate(&context, Duration::from_millis(100), || false);
+                assert_eq!(*results.borrow(), [1]);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn cancels_futures_that_time_out() {
+        let context = MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let calls = Rc::new(Cell::new(0));
+                let counter = Rc::clone(&calls);
+                let fetch = AsyncFetch::new(
+                    move || {
+                        let call = counter.get();
+                        counter.set(call + 1);
+
+                        async move {
+                            if call == 0 {
+                                std::future::pending::<()>().await;
+                            }
+
+                            call
+                        }
+                    },
+                    Duration::from_millis(50),
+                );
+                let results = Rc::new(RefCell::new(Vec::new()));
+                let run = || {
+                    let results = Rc::clone(&results);
+                    fetch.run(move |value| results.borrow_mut().push(value));
+                };
+
+                run();
+                iterate(&context, Duration::from_millis(200), || false);
+
+                run();
+                iterate(&context, Duration::from_secs(5), || {
+                    !results.borrow().is_empty()
+                });
+
+                assert_eq!(*results.borrow(), [1]);
+                assert_eq!(calls.get(), 2);
+            })
+            .unwrap();
+    }
+}